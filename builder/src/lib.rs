@@ -2,6 +2,7 @@ mod utils;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::DeriveInput;
+use syn::spanned::Spanned;
 
 #[proc_macro_derive(Builder, attributes(builder))]
 pub fn derive(input: TokenStream) -> TokenStream {
@@ -9,163 +10,1351 @@ pub fn derive(input: TokenStream) -> TokenStream {
     do_derive(ast).unwrap_or_else(syn::Error::into_compile_error).into()
 }
 
+// One field of the struct (or enum variant) a builder is generated for.
+struct BuilderField<'a> {
+    field: &'a syn::Field,
+    // name of the builder slot, `_0`, `_1`.. for tuple fields
+    ident: syn::Ident,
+    // name of the setter, the slot's name unless prefixed or renamed
+    setter_ident: syn::Ident,
+    // how the field is addressed when the value is assembled in build()
+    member: syn::Member,
+    attrs: utils::FieldAttrs,
+}
+
+impl BuilderField<'_> {
+    fn setter_vis(&self) -> &syn::Visibility {
+        self.attrs.setter.vis.as_ref().unwrap()
+    }
+
+    fn is_required(&self) -> bool {
+        !utils::is_field_optional(self.field) && self.attrs.each.is_none() && self.attrs.default.is_none()
+            && !self.is_skipped() && self.attrs.sub_builder.is_none()
+    }
+
+    // what derived setter names like `clear_field` and `try_field` are built
+    // from, the field's name unless the setter was renamed
+    fn base_ident(&self) -> &syn::Ident {
+        self.attrs.setter.name.as_ref().unwrap_or(&self.ident)
+    }
+
+    // the nested builder type held in the slot of a `sub_builder` field
+    fn sub_builder_ty(&self) -> Option<syn::Type> {
+        self.attrs.sub_builder.as_ref().and_then(|_| utils::sub_builder_type(&self.field.ty))
+    }
+
+    // skipped fields have no slot in the builder and are only filled in by build()
+    fn is_skipped(&self) -> bool {
+        self.attrs.skip.is_some()
+    }
+
+    // an `Option` or `each` field with a default can't tell from its slot
+    // whether `None` or an empty collection was set or left unset, so its
+    // setters flag it in the builder's `__set`
+    fn flags_set(&self) -> bool {
+        self.attrs.default.is_some() && (utils::is_field_optional(self.field) || self.attrs.each.is_some())
+    }
+
+    // parameter type of a setter storing a `ty`, and the expression turning
+    // the argument `arg` into one
+    fn setter_arg(&self, ty: &syn::Type, arg: &syn::Ident) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        if self.attrs.setter.into {
+            (quote!(impl ::core::convert::Into<#ty>), quote!(::core::convert::Into::into(#arg)))
+        } else {
+            (quote!(#ty), quote!(#arg))
+        }
+    }
+
+    // parameters of an `each` setter and the item it extends the collection
+    // with, a `(key, value)` pair for maps
+    fn each_setter_args(&self) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        match utils::extract_each_item_types(self.field).unwrap()[..] {
+            [key_ty, value_ty] => {
+                let key = quote::format_ident!("key");
+                let value = quote::format_ident!("value");
+                let (key_arg_ty, key_value) = self.setter_arg(key_ty, &key);
+                let (value_arg_ty, value_value) = self.setter_arg(value_ty, &value);
+                (quote!(#key: #key_arg_ty, #value: #value_arg_ty), quote!((#key_value, #value_value)))
+            }
+            [item_ty, ..] => {
+                let item = self.attrs.each.as_ref().unwrap();
+                let (arg_ty, value) = self.setter_arg(item_ty, item);
+                (quote!(#item: #arg_ty), value)
+            }
+            [] => unreachable!(),
+        }
+    }
+
+    // what the `each` collection is extended with, `(K, V)` for maps
+    fn each_item_type(&self) -> proc_macro2::TokenStream {
+        match utils::extract_each_item_types(self.field).unwrap()[..] {
+            [key_ty, value_ty] => quote!((#key_ty, #value_ty)),
+            [item_ty, ..] => quote!(#item_ty),
+            [] => unreachable!(),
+        }
+    }
+}
+
+// The value a single builder produces: the struct itself or one enum variant.
+struct BuilderTarget<'a> {
+    builder_ident: syn::Ident,
+    // visibility of the builder, its error type, build() and builder()
+    vis: syn::Visibility,
+    // path used to construct the value in build(), `Command` or `Shape::Circle`
+    ctor_path: proc_macro2::TokenStream,
+    // associated fn on the derived type handing out a fresh builder, if any
+    builder_fn: Option<syn::Ident>,
+    // whether every value of the derived type maps back onto this builder,
+    // which only holds for structs and not for a single enum variant
+    round_trip: bool,
+    fields: Vec<BuilderField<'a>>,
+}
+
 fn do_derive(ast:DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name = &ast.ident;
-    let builder_ident = quote::format_ident!("{}Builder", struct_name);
+    let struct_attrs = utils::get_struct_attrs(&ast)?;
+    let vis = struct_attrs.vis.as_ref().unwrap_or(&ast.vis);
 
-    let fields = utils::derive_get_struct_fields(&ast).unwrap();
-    
-    let gen_builder_fields : Vec<_> = fields.iter().map(|field| {
-        let ident = &field.ident;
-        let ty = &field.ty;
+    match &ast.data {
+        syn::Data::Struct(data) => {
+            let builder_fn = match &struct_attrs.constructor {
+                _ if struct_attrs.no_constructor => None,
+                Some(constructor) => Some(constructor.clone()),
+                None => Some(quote::format_ident!("builder")),
+            };
+            let target = BuilderTarget {
+                builder_ident: struct_attrs.name.clone().unwrap_or_else(|| quote::format_ident!("{}Builder", struct_name)),
+                vis: vis.clone(),
+                ctor_path: quote!(#struct_name),
+                builder_fn,
+                round_trip: true,
+                fields: get_builder_fields(&data.fields, &struct_attrs, vis)?,
+            };
+            gen_builder(&ast, &struct_attrs, &target)
+        }
+        syn::Data::Enum(data) => {
+            if let Some(path) = &struct_attrs.default {
+                return Err(syn::Error::new_spanned(path, "struct-level `builder(default)` is not supported on enums"));
+            }
+            // every variant gets its own builder, so there is no single name to give
+            if let Some(ident) = struct_attrs.name.as_ref().or(struct_attrs.constructor.as_ref()) {
+                return Err(syn::Error::new_spanned(ident, "`name` and `constructor` are not supported on enums"));
+            }
 
-        if utils::is_field(field, "Option".into()) {
-            Ok(quote!(#ident: #ty))
-        } else if let Some(attr_name) = utils::get_each_attr_name(field) {
-            match attr_name {
-                Ok(attr_name) => {
-                    Ok(quote!(#ident: #ty))
-                }
-                Err(e) => {
-                    Err(e)
+            // one builder per variant, handed out by `Shape::circle_builder()`
+            data.variants.iter().map(|variant| {
+                let variant_ident = &variant.ident;
+                let target = BuilderTarget {
+                    builder_ident: quote::format_ident!("{}{}Builder", struct_name, variant_ident),
+                    vis: vis.clone(),
+                    ctor_path: quote!(#struct_name::#variant_ident),
+                    builder_fn: if struct_attrs.no_constructor {
+                        None
+                    } else {
+                        Some(quote::format_ident!(
+                            "{}_builder",
+                            utils::to_snake_case(&variant_ident.to_string()),
+                            span = variant_ident.span()
+                        ))
+                    },
+                    round_trip: false,
+                    fields: get_builder_fields(&variant.fields, &struct_attrs, vis)?,
+                };
+                gen_builder(&ast, &struct_attrs, &target)
+            }).collect()
+        }
+        syn::Data::Union(data) => {
+            Err(syn::Error::new_spanned(data.union_token, "derive(Builder) does not support unions"))
+        }
+    }
+}
+
+fn get_builder_fields<'a>(
+    fields: &'a syn::Fields,
+    struct_attrs: &utils::StructAttrs,
+    vis: &syn::Visibility,
+) -> syn::Result<Vec<BuilderField<'a>>> {
+    fields.iter().enumerate().map(|(index, field)| {
+        let mut attrs = utils::get_field_attrs(field)?;
+        let (ident, member) = match &field.ident {
+            Some(ident) => {
+                if let Some(name) = &attrs.name {
+                    return Err(syn::Error::new_spanned(name, "`builder(name = \"...\")` only applies to tuple fields"));
                 }
+                (ident.clone(), syn::Member::Named(ident.clone()))
+            }
+            None => {
+                let ident = match &attrs.name {
+                    Some(name) => name.clone(),
+                    None => quote::format_ident!("_{}", index, span = field.ty.span()),
+                };
+                (ident, syn::Member::Unnamed(index.into()))
+            }
+        };
+        if attrs.each.is_some() && utils::extract_each_item_types(field).is_none() {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "cannot infer the element type for `builder(each = \"...\")`, expected a collection like `Vec<T>` or `HashMap<K, V>`",
+            ));
+        }
+        if let Some(skip) = &attrs.skip {
+            if attrs.each.is_some() || attrs.default.is_some() {
+                return Err(syn::Error::new_spanned(skip, "`skip` cannot be combined with `each` or `default`"));
+            }
+        }
+        if let Some(sub_builder) = &attrs.sub_builder {
+            if attrs.each.is_some() || attrs.skip.is_some() || attrs.default.is_some() || attrs.try_setter.is_some() {
+                return Err(syn::Error::new_spanned(sub_builder, "`sub_builder` cannot be combined with `each`, `skip`, `default` or `try_setter`"));
+            }
+            // immutable setters and build() only get `&self`, which can't
+            // reach into the nested builder
+            if struct_attrs.pattern == utils::BuilderPattern::Immutable {
+                return Err(syn::Error::new_spanned(sub_builder, "`sub_builder` is not supported with the immutable pattern"));
+            }
+            if utils::is_field_optional(field) || utils::sub_builder_type(&field.ty).is_none() {
+                return Err(syn::Error::new_spanned(&field.ty, "`sub_builder` expects a struct type deriving Builder"));
+            }
+        }
+        if let Some((_, lit)) = &attrs.merge {
+            if attrs.each.is_none() {
+                return Err(syn::Error::new_spanned(lit, "`merge` only applies to `each` fields"));
+            }
+        }
+        if let Some(custom) = &attrs.setter.custom {
+            if !attrs.alias.is_empty() || attrs.try_setter.is_some() {
+                return Err(syn::Error::new_spanned(custom, "`setter(custom)` cannot be combined with `alias` or `try_setter`"));
+            }
+        }
+        if let Some(try_setter) = &attrs.try_setter {
+            if attrs.each.is_some() || attrs.skip.is_some() {
+                return Err(syn::Error::new_spanned(try_setter, "`try_setter` cannot be combined with `each` or `skip`"));
             }
+        }
+        // with a struct-level default, whatever is left unset is taken from the
+        // struct's own `Default` impl instead, also for `Option` and `each`
+        // fields
+        if struct_attrs.default.is_some() && attrs.default.is_none()
+            && attrs.skip.is_none() && attrs.sub_builder.is_none() {
+            attrs.default = Some(syn::parse_quote!(__default.#member));
+        }
+        let setter_ident = match (&attrs.setter.name, attrs.setter.prefix.as_ref().or(struct_attrs.setter.prefix.as_ref())) {
+            (Some(name), _) => name.clone(),
+            (None, Some(prefix)) => utils::prefixed_ident(prefix, &ident),
+            (None, None) => ident.clone(),
+        };
+        attrs.setter.into |= struct_attrs.setter.into;
+        attrs.setter.strip_option = attrs.setter.strip_option.or(struct_attrs.setter.strip_option);
+        if attrs.setter.vis.is_none() {
+            attrs.setter.vis = Some(struct_attrs.setter.vis.as_ref().unwrap_or(vis).clone());
+        }
+        Ok(BuilderField { field, ident, setter_ident, member, attrs })
+    }).collect()
+}
+
+fn gen_builder(ast: &DeriveInput, struct_attrs: &utils::StructAttrs, target: &BuilderTarget) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &ast.ident;
+    let builder_ident = &target.builder_ident;
+    let builder_fn = &target.builder_fn;
+    let ctor_path = &target.ctor_path;
+    let vis = &target.vis;
+    let error_ident = quote::format_ident!("{}Error", builder_ident);
+    let build_fn = struct_attrs.build_fn.name.clone().unwrap_or_else(|| quote::format_ident!("build"));
+    // everything else comes from `core`, but `String` and `format!` live in
+    // `alloc` for no_std crates, which declare `extern crate alloc;` themselves
+    let alloc = if struct_attrs.no_std { quote!(::alloc) } else { quote!(::std) };
+
+    let fields = &target.fields;
+    // https://docs.rs/syn/1.0.93/syn/struct.Generics.html#method.split_for_impl
+    let generics = &ast.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // the fields that get a slot and setters in the builder
+    let slots: Vec<_> = fields.iter().filter(|field| !field.is_skipped()).collect();
+    let required_fields: Vec<_> = fields.iter().filter(|field| field.is_required()).collect();
+
+    // in typestate mode every required field gets its own type parameter on the
+    // builder, `()` while unset and the field type once the setter was called
+    let typestate_params: Vec<_> = required_fields.iter().map(|field| utils::typestate_param_ident(&field.ident)).collect();
+    let typestate_set_types: Vec<_> = required_fields.iter().map(|field| &field.field.ty).collect();
+
+    let mut gen_builder_fields : Vec<_> = slots.iter().map(|field| {
+        let ident = &field.ident;
+        let ty = &field.field.ty;
+        let field_attr = &field.attrs.field_attr;
+
+        let slot_ty = if let Some(sub_ty) = field.sub_builder_ty() {
+            quote!(#sub_ty)
+        } else if struct_attrs.typestate && field.is_required() {
+            let param = utils::typestate_param_ident(ident);
+            quote!(#param)
+        } else if utils::is_field_optional(field.field) || field.attrs.each.is_some() {
+            quote!(#ty)
         } else {
-            Ok(quote!(#ident : Option<#ty>))
+            quote!(::core::option::Option<#ty>)
+        };
+        quote!{
+            #(#[#field_attr])*
+            #ident: #slot_ty
         }
-    }).collect::<syn::Result<Vec<_>>>()?;
+    }).collect();
+    // `__set` flags the slots whose type can't tell whether they were set:
+    // typestate slots when the field's own type is `()`, and those of
+    // `Option` and `each` fields with a default. The typestate ones come first.
+    let typestate_fields = if struct_attrs.typestate { &required_fields[..] } else { &[][..] };
+    let set_fields: Vec<_> = typestate_fields.iter().copied().chain(slots.iter().copied().filter(|field| field.flags_set())).collect();
+    let set_count = set_fields.len();
+    let set_index = |field: &BuilderField| set_fields.iter().position(|set_field| set_field.ident == field.ident);
+    gen_builder_fields.push(quote!(__set: [bool; #set_count]));
+
+    let derive = &struct_attrs.derive;
+    let struct_attr = &struct_attrs.struct_attr;
+    let gen_builder_attrs = if derive.is_empty() {
+        quote!(#(#[#struct_attr])*)
+    } else {
+        quote!{
+            #[derive(#(#derive),*)]
+            #(#[#struct_attr])*
+        }
+    };
 
-    let gen_builder_default : Vec<_> = fields.iter().map(|field| {
+    let mut gen_builder_default : Vec<_> = slots.iter().map(|field| {
         let ident = &field.ident;
-        let field_type =  &field.ty;
-        if utils::get_each_attr_name(field).is_some() {
+        let field_type =  &field.field.ty;
+        if let Some(sub_ty) = field.sub_builder_ty() {
             quote! {
-                #ident : <#field_type>::new()
+                #ident : <#sub_ty as ::core::default::Default>::default()
             }
+        } else if field.attrs.each.is_some() {
+            quote! {
+                #ident : <#field_type as ::core::default::Default>::default()
+            }
+        } else if struct_attrs.typestate && field.is_required() {
+            quote!(
+                #ident : ()
+            )
         } else {
             quote!(
-                #ident : None
+                #ident : ::core::option::Option::None
             )
         }
     }).collect();
+    gen_builder_default.push(quote!(__set: [false; #set_count]));
 
-    let gen_setters: Vec<_> = fields.iter().map(|field| {
+    // a typestate builder changes type along the chain, so it is always owned
+    let pattern = if struct_attrs.typestate { utils::BuilderPattern::Owned } else { struct_attrs.pattern };
+    let mut gen_setters: Vec<_> = slots.iter().map(|field| {
         let ident = &field.ident;
-        let ty = &field.ty;
-        if utils::is_field_optional(field) {
-            if let Some(inner_ty) = utils::extract_inner_type(field, "Option".into()) {
-                Ok(quote!{
-                    fn #ident(&mut self, #ident: #inner_ty) -> &mut Self {
-                        self.#ident = Some(#ident);
-                        self
-                    }
+        let setter_ident = &field.setter_ident;
+        let ty = &field.field.ty;
+        let gen_flag_set = |this: &proc_macro2::TokenStream, set: bool| match set_index(field) {
+            Some(index) if field.flags_set() => quote!(#this.__set[#index] = #set;),
+            _ => quote!(),
+        };
+        if field.attrs.setter.custom.is_some() {
+            // written by hand in the user's own impl block
+            quote!()
+        } else if struct_attrs.typestate && field.is_required() {
+            // generated by gen_typestate() as a state transition
+            quote!()
+        } else if let Some(sub_ty) = field.sub_builder_ty() {
+            // `tls(|b| b.cert(..))` edits the nested builder in place and
+            // `tls_mut()` hands it out; both expect it to use the mutable pattern
+            let gen_edit_setter = gen_with_aliases(field, setter_ident, |name| {
+                gen_setter(pattern, field.setter_vis(), name, quote!(#ident: impl for<'__b> ::core::ops::FnOnce(&'__b mut #sub_ty) -> &'__b mut #sub_ty), |this| quote!{
+                    #ident(&mut #this.#ident);
                 })
+            });
+            let mut_ident = quote::format_ident!("{}_mut", field.base_ident());
+            let setter_vis = field.setter_vis();
+            quote!{
+                #gen_edit_setter
+                #setter_vis fn #mut_ident(&mut self) -> &mut #sub_ty {
+                    &mut self.#ident
+                }
+            }
+        } else if utils::is_field_optional(field.field) {
+            if let Some(inner_ty) = utils::extract_inner_type(field.field, "Option".into()) {
+                // `current_dir_opt` takes the `Option<T>` as is and
+                // `clear_current_dir` unsets the field again
+                let clear_ident = utils::prefixed_ident("clear_", field.base_ident());
+                let gen_clear_setter = gen_setter(pattern, field.setter_vis(), &clear_ident, quote!(), |this| {
+                    let gen_flag = gen_flag_set(this, false);
+                    quote!{
+                        #this.#ident = ::core::option::Option::None;
+                        #gen_flag
+                    }
+                });
+                if field.attrs.setter.strip_option == Some(false) {
+                    let (arg_ty, value) = field.setter_arg(ty, ident);
+                    let gen_plain_setter = gen_with_aliases(field, setter_ident, |name| gen_setter(pattern, field.setter_vis(), name, quote!(#ident: #arg_ty), |this| {
+                        let gen_flag = gen_flag_set(this, true);
+                        quote!{
+                            #this.#ident = #value;
+                            #gen_flag
+                        }
+                    }));
+                    let gen_try_setter = gen_pattern_try_setter(field, inner_ty, pattern, &error_ident, &alloc);
+                    quote!{
+                        #gen_plain_setter
+                        #gen_try_setter
+                        #gen_clear_setter
+                    }
+                } else {
+                    let (arg_ty, value) = field.setter_arg(inner_ty, ident);
+                    let gen_plain_setter = gen_with_aliases(field, setter_ident, |name| gen_setter(pattern, field.setter_vis(), name, quote!(#ident: #arg_ty), |this| {
+                        let gen_flag = gen_flag_set(this, true);
+                        quote!{
+                            #this.#ident = ::core::option::Option::Some(#value);
+                            #gen_flag
+                        }
+                    }));
+                    let opt_ident = quote::format_ident!("{}_opt", setter_ident);
+                    let gen_opt_setter = gen_setter(pattern, field.setter_vis(), &opt_ident, quote!(#ident: #ty), |this| {
+                        let gen_flag = gen_flag_set(this, true);
+                        quote!{
+                            #this.#ident = #ident;
+                            #gen_flag
+                        }
+                    });
+                    let gen_try_setter = gen_pattern_try_setter(field, inner_ty, pattern, &error_ident, &alloc);
+                    quote!{
+                        #gen_plain_setter
+                        #gen_try_setter
+                        #gen_opt_setter
+                        #gen_clear_setter
+                    }
+                }
             } else {
-                Ok(quote!())
-            }
-        } else if let Some(Ok(attr_name)) = utils::get_each_attr_name(field) {
-            let inner_type = utils::extract_inner_type(field, "Vec".into());
-            let attr_ident = quote::format_ident!("{}", attr_name);
-            Ok(quote!(
-                fn #attr_ident(&mut self, #attr_ident: #inner_type) -> &mut Self {
-                    self.#ident.push(#attr_ident);
-                    self
+                quote!()
+            }
+        } else if let Some(attr_ident) = &field.attrs.each {
+            let (args, item) = field.each_setter_args();
+            let each_setter = |name: &syn::Ident| gen_setter(pattern, field.setter_vis(), name, args.clone(), |this| {
+                let gen_flag = gen_flag_set(this, true);
+                quote!{
+                    ::core::iter::Extend::extend(&mut #this.#ident, ::core::iter::once(#item));
+                    #gen_flag
+                }
+            });
+            // bulk setters next to the single element one: `args` replaces the
+            // whole collection, `extend_args` appends to it
+            let item_ty = field.each_item_type();
+            let items = quote::format_ident!("items");
+            let extend_ident = utils::prefixed_ident("extend_", field.base_ident());
+            let gen_extend_setter = gen_setter(pattern, field.setter_vis(), &extend_ident, quote!(#items: impl ::core::iter::IntoIterator<Item = #item_ty>), |this| {
+                let gen_flag = gen_flag_set(this, true);
+                quote!{
+                    ::core::iter::Extend::extend(&mut #this.#ident, #items);
+                    #gen_flag
                 }
-            ))
+            });
+            // when `each` reuses the setter's name the single element setter
+            // keeps it, along with its aliases, and there is no replacing setter
+            let (gen_each_setter, gen_replace_setter) = if attr_ident == setter_ident {
+                (gen_with_aliases(field, attr_ident, each_setter), quote!())
+            } else {
+                (each_setter(attr_ident), gen_with_aliases(field, setter_ident, |name| {
+                    gen_setter(pattern, field.setter_vis(), name, quote!(#items: impl ::core::iter::IntoIterator<Item = #item_ty>), |this| {
+                        let gen_flag = gen_flag_set(this, true);
+                        quote!{
+                            #this.#ident = ::core::default::Default::default();
+                            ::core::iter::Extend::extend(&mut #this.#ident, #items);
+                            #gen_flag
+                        }
+                    })
+                }))
+            };
+            quote!{
+                #gen_each_setter
+                #gen_replace_setter
+                #gen_extend_setter
+            }
         } else {
-            Ok(quote!{
-                fn #ident(&mut self, #ident: #ty) -> &mut Self {
-                    self.#ident = Some(#ident);
-                    self
+            let (arg_ty, value) = field.setter_arg(ty, ident);
+            let gen_plain_setter = gen_with_aliases(field, setter_ident, |name| gen_setter(pattern, field.setter_vis(), name, quote!(#ident: #arg_ty), |this| quote!{
+                #this.#ident = ::core::option::Option::Some(#value);
+            }));
+            let gen_try_setter = gen_pattern_try_setter(field, ty, pattern, &error_ident, &alloc);
+            quote!{
+                #gen_plain_setter
+                #gen_try_setter
+            }
+        }
+    }).collect();
+
+    // a typestate builder cannot reach build() with a field missing
+    let missing_fields = if struct_attrs.typestate { &[][..] } else { &required_fields[..] };
+
+    let gen_error_variants: Vec<_> = missing_fields.iter().map(|field| {
+        let variant = utils::missing_variant_ident(&field.ident);
+        quote!(#variant)
+    }).collect();
+
+    let gen_error_display: Vec<_> = missing_fields.iter().map(|field| {
+        let variant = utils::missing_variant_ident(&field.ident);
+        let missing_msg = format!("Field `{}` is missing", utils::field_name_literal(&field.ident));
+        quote!{
+            #error_ident::#variant => f.write_str(#missing_msg)
+        }
+    }).collect();
+
+    let gen_missing_field: Vec<_> = missing_fields.iter().map(|field| {
+        let variant = utils::missing_variant_ident(&field.ident);
+        let field_name = utils::field_name_literal(&field.ident);
+        quote!{
+            #error_ident::#variant => ::core::option::Option::Some(#alloc::string::String::from(#field_name))
+        }
+    }).collect();
+
+    // a nested builder failing to build is wrapped into an `InvalidTls`
+    // variant, which reports a missing field as `tls.cert`
+    let sub_fields: Vec<_> = slots.iter().filter(|field| field.attrs.sub_builder.is_some()).collect();
+    let gen_sub_error_variants: Vec<_> = sub_fields.iter().map(|field| {
+        let variant = utils::invalid_variant_ident(&field.ident);
+        let sub_error_ty = utils::sub_builder_error_type(&field.field.ty).unwrap();
+        quote!(#variant(#sub_error_ty))
+    }).collect();
+    let gen_sub_error_display: Vec<_> = sub_fields.iter().map(|field| {
+        let variant = utils::invalid_variant_ident(&field.ident);
+        quote!{
+            #error_ident::#variant(err) => match self.missing_field() {
+                ::core::option::Option::Some(path) => ::core::write!(f, "Field `{}` is missing", path),
+                ::core::option::Option::None => ::core::fmt::Display::fmt(err, f),
+            }
+        }
+    }).collect();
+    let gen_sub_missing_field: Vec<_> = sub_fields.iter().map(|field| {
+        let variant = utils::invalid_variant_ident(&field.ident);
+        let path_format = format!("{}.{{}}", utils::field_name_literal(&field.ident));
+        quote!{
+            #error_ident::#variant(err) => err.missing_field().map(|path| #alloc::format!(#path_format, path))
+        }
+    }).collect();
+
+    let error_doc = format!("Error returned by [`{}::{}`].", builder_ident, build_fn);
+    let gen_error = quote!{
+        #[doc = #error_doc]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #vis enum #error_ident {
+            #(#gen_error_variants,)*
+            #(#gen_sub_error_variants,)*
+            ValidationError(#alloc::string::String),
+        }
+
+        impl #error_ident {
+            /// The field whose absence made the build fail, as a dotted path
+            /// like `tls.cert` for a field of a nested builder.
+            #vis fn missing_field(&self) -> ::core::option::Option<#alloc::string::String> {
+                match self {
+                    #(#gen_missing_field,)*
+                    #(#gen_sub_missing_field,)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+
+        impl ::core::fmt::Display for #error_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match self {
+                    #(#gen_error_display,)*
+                    #(#gen_sub_error_display,)*
+                    #error_ident::ValidationError(msg) => f.write_str(msg),
+                }
+            }
+        }
+
+        impl ::core::error::Error for #error_ident {}
+
+        impl ::core::convert::From<#alloc::string::String> for #error_ident {
+            fn from(msg: #alloc::string::String) -> Self {
+                #error_ident::ValidationError(msg)
+            }
+        }
+    };
+
+    // build() binds every field to a local in declaration order, so a
+    // `default = "..."` expression can refer to the fields before it
+    let gen_build_fields = |access: SlotAccess| -> Vec<proc_macro2::TokenStream> {
+        fields.iter().map(|field| {
+            let ident = &field.ident;
+            let slot = match access {
+                SlotAccess::Clone => quote!(self.#ident.clone()),
+                SlotAccess::Move => quote!(self.#ident),
+                SlotAccess::Take if field.attrs.each.is_some() => quote!(::core::mem::take(&mut self.#ident)),
+                SlotAccess::Take => quote!(self.#ident.take()),
+            };
+            let gen_bind = if let Some(skip) = &field.attrs.skip {
+                quote!(let #ident = #skip;)
+            } else if field.attrs.sub_builder.is_some() {
+                let sub_build = match access {
+                    SlotAccess::Clone => quote!(self.#ident.build()),
+                    SlotAccess::Move => quote!({
+                        #[allow(unused_mut)]
+                        let mut __sub_builder = self.#ident;
+                        __sub_builder.build()
+                    }),
+                    SlotAccess::Take => quote!(::core::mem::take(&mut self.#ident).build()),
+                };
+                let variant = utils::invalid_variant_ident(ident);
+                quote!{
+                    let #ident = match #sub_build {
+                        ::core::result::Result::Ok(value) => value,
+                        ::core::result::Result::Err(err) => return ::core::result::Result::Err(#error_ident::#variant(err)),
+                    };
                 }
-            })
+            } else if struct_attrs.typestate && field.is_required() {
+                quote!(let #ident = #slot;)
+            } else if field.is_required() {
+                let variant = utils::missing_variant_ident(ident);
+                quote!{
+                    let #ident = match #slot {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None => return ::core::result::Result::Err(#error_ident::#variant),
+                    };
+                }
+            } else if let (Some(default), true) = (&field.attrs.default, field.flags_set()) {
+                // only a slot that was never set falls back to the default,
+                // an explicit `None` or empty collection is kept
+                let index = set_index(field).unwrap();
+                let is_set = match access {
+                    SlotAccess::Take => quote!(::core::mem::replace(&mut self.__set[#index], false)),
+                    _ => quote!(self.__set[#index]),
+                };
+                quote!{
+                    let #ident = if #is_set { #slot } else { #default };
+                }
+            } else if let Some(default) = &field.attrs.default {
+                quote!{
+                    let #ident = match #slot {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None => #default,
+                    };
+                }
+            } else {
+                quote!(let #ident = #slot;)
+            };
+            match &field.attrs.validate {
+                Some(validate) => quote!{
+                    #gen_bind
+                    #validate(&#ident)?;
+                },
+                None => gen_bind,
+            }
+        }).collect()
+    };
+    // the struct-level validator sees the builder before anything is moved
+    // out of it
+    let gen_build_validate = match &struct_attrs.build_fn.validate {
+        Some(validate) => quote!(#validate(&self)?;),
+        None => quote!(),
+    };
+    let field_idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let field_members: Vec<_> = fields.iter().map(|field| &field.member).collect();
+    let gen_struct_default = if struct_attrs.default.is_some() {
+        quote!(let __default: #struct_name #ty_generics = ::core::default::Default::default();)
+    } else {
+        quote!()
+    };
+    let gen_build_ctor = quote!{
+        ::core::result::Result::Ok(
+            #ctor_path{
+                #(#field_members: #field_idents),*
+            }
+        )
+    };
+
+    // `From<Command>` and `Command::to_builder()` put every field back into
+    // its slot, so the builder can be used to modify an existing value
+    let slot_idents: Vec<_> = slots.iter().map(|field| &field.ident).collect();
+    let slot_types: Vec<_> = slots.iter().map(|field| &field.field.ty).collect();
+    let gen_round_trip = |builder_ty: proc_macro2::TokenStream| {
+        if !target.round_trip {
+            return quote!();
         }
-    }).collect::<syn::Result<Vec<_>>>()?;
-    
-    let gen_build_check_err: Vec<_> = fields.iter().map(|field| {
-        let field_name = &field.ident;
-        let missing_msg = format!("Field {:?} is missing", field_name);
-        if utils::is_field_optional(field) {
+        let mut gen_slots: Vec<_> = slots.iter().map(|field| {
+            let ident = &field.ident;
+            if field.attrs.sub_builder.is_some() {
+                quote!(#ident: ::core::convert::From::from(#ident))
+            } else if (struct_attrs.typestate && field.is_required())
+                || utils::is_field_optional(field.field)
+                || field.attrs.each.is_some() {
+                quote!(#ident)
+            } else {
+                quote!(#ident: ::core::option::Option::Some(#ident))
+            }
+        }).collect();
+        gen_slots.push(quote!(__set: [true; #set_count]));
+        // `executable` for named fields, `0: _0` for tuple fields, skipped
+        // fields are dropped
+        let gen_patterns: Vec<_> = fields.iter().map(|field| {
+            let ident = &field.ident;
+            match &field.member {
+                member if field.is_skipped() => quote!(#member: _),
+                syn::Member::Named(_) => quote!(#ident),
+                syn::Member::Unnamed(index) => quote!(#index: #ident),
+            }
+        }).collect();
+        quote!{
+            impl #impl_generics ::core::convert::From<#struct_name #ty_generics> for #builder_ty #where_clause {
+                fn from(value: #struct_name #ty_generics) -> Self {
+                    let #ctor_path { #(#gen_patterns),* } = value;
+                    #builder_ident {
+                        #(#gen_slots,)*
+                        __phantom: ::core::marker::PhantomData,
+                    }
+                }
+            }
+
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                // the bounds are higher-ranked so that a struct with a field
+                // that isn't Clone only loses this method instead of failing
+                // to compile on a trivially false where-clause
+                #vis fn to_builder(&self) -> #builder_ty
+                where
+                    #(for<'__to_builder> #slot_types: ::core::clone::Clone),*
+                {
+                    let #ctor_path { #(#gen_patterns),* } = self;
+                    #(let #slot_idents = ::core::clone::Clone::clone(#slot_idents);)*
+                    #builder_ident {
+                        #(#gen_slots,)*
+                        __phantom: ::core::marker::PhantomData,
+                    }
+                }
+            }
+        }
+    };
+
+    // a setter, alias or `each` setter of a field takes precedence over a
+    // generated method of the same name like `new()` or `merge()`, which is
+    // left out then
+    let setter_takes = |name: &str| slots.iter().any(|field| {
+        field.setter_ident == name
+            || field.attrs.alias.iter().any(|alias| *alias == name)
+            || field.attrs.each.as_ref().is_some_and(|each| *each == name)
+    });
+
+    // `Builder::new()`, and `Default` unless it is derived, hand out a builder
+    // with nothing set yet; `Command::builder()` goes through `Default`.
+    let derives_default = utils::derives_trait(derive, "Default");
+    let gen_builder_new = || {
+        let gen_new_body = quote!{
+            #builder_ident {
+                #(#gen_builder_default,)*
+                __phantom: ::core::marker::PhantomData,
+            }
+        };
+        let gen_new_fn = if setter_takes("new") {
             quote!()
-        } else if utils::get_each_attr_name(field).is_some() {
+        } else {
+            quote!{
+                impl #impl_generics #builder_ident #ty_generics #where_clause {
+                    #vis fn new() -> Self {
+                        #gen_new_body
+                    }
+                }
+            }
+        };
+        let gen_builder_default_impl = if derives_default {
             quote!()
         } else {
             quote!{
-                if let std::option::Option::None = self.#field_name {
-                    return std::result::Result::Err(#missing_msg.into())
+                impl #impl_generics ::core::default::Default for #builder_ident #ty_generics #where_clause {
+                    fn default() -> Self {
+                        #gen_new_body
+                    }
                 }
             }
+        };
+        quote!{
+            #gen_new_fn
+            #gen_builder_default_impl
         }
-    }).collect();
+    };
 
-    let gen_build_body: Vec<_> = fields.iter().map(|field| {
+    // `get_port()` reads back what was set so far: `None` for an unset slot,
+    // the collection (as a slice for a `Vec`) for `each` fields and the nested
+    // builder for `sub_builder` ones. A typestate slot is `&()` until set.
+    gen_setters.extend(slots.iter().map(|field| {
         let ident = &field.ident;
-        // let ty = &field.ty;
-        if utils::is_field_optional(field) {
-            quote!{
-                #ident : self.#ident.clone()
-            }
-        } else if utils::get_each_attr_name(field).is_some() {
-            quote!{
-                #ident : self.#ident.clone()
+        let ty = &field.field.ty;
+        let getter_ident = utils::prefixed_ident("get_", field.base_ident());
+        let (ret_ty, value) = if let Some(sub_ty) = field.sub_builder_ty() {
+            (quote!(&#sub_ty), quote!(&self.#ident))
+        } else if struct_attrs.typestate && field.is_required() {
+            let param = utils::typestate_param_ident(ident);
+            (quote!(&#param), quote!(&self.#ident))
+        } else if field.attrs.each.is_some() {
+            match utils::extract_inner_type(field.field, "Vec".into()) {
+                Some(item_ty) => (quote!(&[#item_ty]), quote!(&self.#ident[..])),
+                None => (quote!(&#ty), quote!(&self.#ident)),
             }
         } else {
-            quote!{
-                #ident : self.#ident.clone().unwrap()
+            let value_ty = if utils::is_field_optional(field.field) {
+                utils::extract_inner_type(field.field, "Option".into()).unwrap_or(ty)
+            } else {
+                ty
+            };
+            (quote!(::core::option::Option<&#value_ty>), quote!(self.#ident.as_ref()))
+        };
+        let getter_vis = field.setter_vis();
+        quote!{
+            #getter_vis fn #getter_ident(&self) -> #ret_ty {
+                #value
             }
         }
+    }).collect::<Vec<_>>());
+
+    // `merge()` layers another builder over this one: whatever is set there
+    // wins, `each` collections are appended to or replaced, and nested
+    // builders are merged in turn. Typestate builders would change type along
+    // the way and don't get it. `merged()` goes along with `merge()`.
+    if !struct_attrs.typestate && !setter_takes("merge") && !setter_takes("merged") {
+        let gen_merge_fields = slots.iter().map(|field| {
+            let ident = &field.ident;
+            if field.attrs.sub_builder.is_some() {
+                quote!(self.#ident.merge(other.#ident);)
+            } else if let Some(index) = set_index(field) {
+                // a flagged slot counts as set even when `None` or empty
+                let appends = field.attrs.each.is_some()
+                    && !matches!(field.attrs.merge, Some((utils::MergeMode::Replace, _)));
+                if appends {
+                    quote!{
+                        ::core::iter::Extend::extend(&mut self.#ident, other.#ident);
+                        self.__set[#index] |= other.__set[#index];
+                    }
+                } else {
+                    quote!{
+                        if other.__set[#index] {
+                            self.#ident = other.#ident;
+                            self.__set[#index] = true;
+                        }
+                    }
+                }
+            } else if field.attrs.each.is_some() {
+                match field.attrs.merge {
+                    Some((utils::MergeMode::Replace, _)) => quote!{
+                        let mut items = ::core::iter::IntoIterator::into_iter(other.#ident).peekable();
+                        if items.peek().is_some() {
+                            self.#ident = ::core::default::Default::default();
+                            ::core::iter::Extend::extend(&mut self.#ident, items);
+                        }
+                    },
+                    _ => quote!(::core::iter::Extend::extend(&mut self.#ident, other.#ident);),
+                }
+            } else {
+                quote!{
+                    if other.#ident.is_some() {
+                        self.#ident = other.#ident;
+                    }
+                }
+            }
+        });
+        gen_setters.push(quote!{
+            /// Takes over everything set on `other`, which wins over what is
+            /// already set here.
+            #vis fn merge(&mut self, other: Self) -> &mut Self {
+                #(#gen_merge_fields)*
+                self
+            }
+
+            /// [`merge`](Self::merge) for a builder held by value.
+            #vis fn merged(mut self, other: Self) -> Self {
+                self.merge(other);
+                self
+            }
+        });
+    }
+
+    // `FIELDS`, `missing_fields()` and `is_complete()` tell what can be set and
+    // what still has to be before build() succeeds. They go along with the
+    // setters, which in typestate mode are implemented for every state, and
+    // like `merge()` give way to a setter of the same name.
+    let field_info_ident = quote::format_ident!("{}FieldInfo", builder_ident);
+    let field_kind_ident = quote::format_ident!("{}FieldKind", builder_ident);
+    let gen_field_infos: Vec<_> = slots.iter().map(|field| {
+        let name = utils::field_name_literal(&field.ident);
+        let ty = utils::type_to_string(&field.field.ty);
+        let kind = if field.attrs.each.is_some() {
+            quote::format_ident!("Each")
+        } else if field.is_required() || field.attrs.sub_builder.is_some() {
+            quote::format_ident!("Required")
+        } else {
+            quote::format_ident!("Optional")
+        };
+        quote!{
+            #field_info_ident { name: #name, ty: #ty, kind: #field_kind_ident::#kind }
+        }
     }).collect();
+    // a nested builder counts as missing as a whole while it is incomplete
+    let (missing_names, missing_checks): (Vec<_>, Vec<_>) = slots.iter().filter_map(|field| {
+        let ident = &field.ident;
+        let check = if field.attrs.sub_builder.is_some() {
+            quote!(!self.#ident.is_complete())
+        } else if struct_attrs.typestate && field.is_required() {
+            let index = set_index(field).unwrap();
+            quote!(!self.__set[#index])
+        } else if field.is_required() {
+            quote!(self.#ident.is_none())
+        } else {
+            return None;
+        };
+        Some((utils::field_name_literal(ident), check))
+    }).unzip();
+    let gen_is_complete = if missing_checks.is_empty() {
+        quote!(true)
+    } else {
+        quote!(#(!(#missing_checks))&&*)
+    };
+    gen_setters.push(quote!{
+        /// Every field that can be set on this builder, in declaration order.
+        #vis const FIELDS: &'static [#field_info_ident] = &[#(#gen_field_infos),*];
+    });
+    if !setter_takes("missing_fields") {
+        gen_setters.push(quote!{
+            /// Names of the required fields that are not set yet.
+            #vis fn missing_fields(&self) -> #alloc::vec::Vec<&'static str> {
+                let mut missing = #alloc::vec::Vec::new();
+                #(
+                    if #missing_checks {
+                        missing.push(#missing_names);
+                    }
+                )*
+                missing
+            }
+        });
+    }
+    if !setter_takes("is_complete") {
+        gen_setters.push(quote!{
+            /// Whether every required field is set.
+            #vis fn is_complete(&self) -> bool {
+                #gen_is_complete
+            }
+        });
+    }
+    let field_info_doc = format!("A field of [`{}`], as listed in its `FIELDS`.", builder_ident);
+    let field_kind_doc = format!("Whether a field of [`{}`] has to be set.", builder_ident);
+    let gen_field_info = quote!{
+        #[doc = #field_info_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #vis struct #field_info_ident {
+            pub name: &'static str,
+            // the field's type as written in the struct
+            pub ty: &'static str,
+            pub kind: #field_kind_ident,
+        }
+
+        #[doc = #field_kind_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #vis enum #field_kind_ident {
+            // build() fails without it, also nested builders
+            Required,
+            // falls back to `None` or a default
+            Optional,
+            // a collection filled item by item
+            Each,
+        }
+    };
+
+    if struct_attrs.typestate {
+        let gen_build_fields = gen_build_fields(SlotAccess::Move);
+        let gen_build_body = quote!{
+            #gen_build_validate
+            #gen_struct_default
+            #(#gen_build_fields)*
+            #gen_build_ctor
+        };
+        let derive = gen_typestate(
+            ast,
+            target,
+            &error_ident,
+            &alloc,
+            &typestate_params,
+            &typestate_set_types,
+            &gen_builder_attrs,
+            &gen_builder_fields,
+            &gen_setters,
+            &build_fn,
+            &gen_build_body,
+            &gen_error,
+        );
+        let gen_builder_new = gen_builder_new();
+        let struct_args = utils::generic_args(generics);
+        let gen_round_trip = gen_round_trip(quote!(#builder_ident<#(#struct_args,)* #(#typestate_set_types),*>));
+        return Ok(quote!{
+            #derive
+            #gen_field_info
+            #gen_builder_new
+            #gen_round_trip
+        });
+    }
+
+    // field types need `Clone` wherever the builder's slots get cloned; the
+    // bound goes on those methods only instead of on the whole builder
+    let (build_receiver, access) = match pattern {
+        utils::BuilderPattern::Owned => (quote!(self), SlotAccess::Move),
+        utils::BuilderPattern::Immutable => (quote!(&self), SlotAccess::Clone),
+        utils::BuilderPattern::Mutable if struct_attrs.build_fn.take => (quote!(&mut self), SlotAccess::Take),
+        utils::BuilderPattern::Mutable => (quote!(&mut self), SlotAccess::Clone),
+    };
+    let build_bounds = match access {
+        SlotAccess::Clone => {
+            // nested builders are built in place rather than cloned
+            let cloned_types = slots.iter().filter(|field| field.attrs.sub_builder.is_none()).map(|field| &field.field.ty);
+            quote!(where #(#cloned_types: ::core::clone::Clone),*)
+        }
+        _ => quote!(),
+    };
+    // taking out of the slots one by one would leave the builder half emptied
+    // when a later field turns out to be missing, so check them all first
+    let gen_build_check_err: Vec<_> = match access {
+        SlotAccess::Take => required_fields.iter().map(|field| {
+            let field_name = &field.ident;
+            let variant = utils::missing_variant_ident(field_name);
+            quote!{
+                if let ::core::option::Option::None = self.#field_name {
+                    return ::core::result::Result::Err(#error_ident::#variant)
+                }
+            }
+        }).collect(),
+        _ => Vec::new(),
+    };
+    let gen_build_fields = gen_build_fields(access);
 
     let gen_build = quote!{
-        fn build(&mut self) -> std::result::Result<#struct_name, std::boxed::Box<dyn std::error::Error>> {
+        #vis fn #build_fn(#build_receiver) -> ::core::result::Result<#struct_name #ty_generics, #error_ident>
+        #build_bounds
+        {
+            #gen_build_validate
             #(#gen_build_check_err)*
-            std::result::Result::Ok(
-                #struct_name{
-                    #(#gen_build_body),*
+            #gen_struct_default
+            #(#gen_build_fields)*
+            #gen_build_ctor
+        }
+    };
+
+    // immutable setters hand out an updated copy of the builder, unless Clone
+    // is already derived for it
+    let derives_clone = utils::derives_trait(derive, "Clone");
+    let gen_builder_clone = if pattern == utils::BuilderPattern::Immutable && !derives_clone {
+        let mut clone_generics = generics.clone();
+        let clone_where = clone_generics.make_where_clause();
+        for field in &slots {
+            let ty = field.sub_builder_ty().unwrap_or_else(|| field.field.ty.clone());
+            clone_where.predicates.push(syn::parse_quote!(#ty: ::core::clone::Clone));
+        }
+        let (_, _, clone_where_clause) = clone_generics.split_for_impl();
+        quote!{
+            impl #impl_generics ::core::clone::Clone for #builder_ident #ty_generics #clone_where_clause {
+                fn clone(&self) -> Self {
+                    #builder_ident {
+                        #(#slot_idents: ::core::clone::Clone::clone(&self.#slot_idents),)*
+                        __set: self.__set,
+                        __phantom: ::core::marker::PhantomData,
+                    }
                 }
-            )
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let gen_builder_fn = match builder_fn {
+        Some(builder_fn) => quote!{
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                #vis fn #builder_fn() -> #builder_ident #ty_generics {
+                    <#builder_ident #ty_generics as ::core::default::Default>::default()
+                }
+            }
+        },
+        None => quote!(),
+    };
+
+    let derive = quote!{
+        #gen_builder_attrs
+        #vis struct #builder_ident #generics #where_clause {
+            #(#gen_builder_fields,)*
+            // skipped fields take their slots with them, so the struct's
+            // generics are not necessarily used by any other field
+            __phantom: ::core::marker::PhantomData<fn() -> #struct_name #ty_generics>,
         }
+
+        impl #impl_generics #builder_ident #ty_generics #where_clause {
+            #(#gen_setters)*
+            #gen_build
+        }
+
+        #gen_builder_clone
+
+        #gen_error
+
+        #gen_builder_fn
     };
+    let gen_builder_new = gen_builder_new();
+    let gen_round_trip = gen_round_trip(quote!(#builder_ident #ty_generics));
 
-    // eprintln!("{:#?}", fields);
+    Ok(quote!{
+        #derive
+        #gen_field_info
+        #gen_builder_new
+        #gen_round_trip
+    })
+}
 
-    /*
-    let gen_fn_each: Vec<_>= fields.iter().map(|field| {
-        let ident = &field.ident;
-        if let Some(Ok(attr_name)) = utils::get_each_attr_name(field) {
-            let inner_type = utils::extract_inner_type(field, "Vec".into());
-            let attr_ident = quote::format_ident!("{}", attr_name);
+// How build() gets the value out of a builder slot.
+#[derive(Clone, Copy)]
+enum SlotAccess {
+    Clone,
+    Move,
+    Take,
+}
 
-            quote!(
-                fn #attr_ident(&mut self, #attr_ident: #inner_type) -> &mut Self {
-                    self.#ident.push(#attr_ident);
+// Wraps a setter body into the method shape of the builder pattern. The body
+// is written against `this`, which is `self` or the copy an immutable setter
+// returns.
+fn gen_setter(
+    pattern: utils::BuilderPattern,
+    vis: &syn::Visibility,
+    name: &syn::Ident,
+    args: proc_macro2::TokenStream,
+    body: impl Fn(&proc_macro2::TokenStream) -> proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    match pattern {
+        utils::BuilderPattern::Mutable => {
+            let body = body(&quote!(self));
+            quote!{
+                #vis fn #name(&mut self, #args) -> &mut Self {
+                    #body
                     self
                 }
-            )
-        } else {
-            quote!()
+            }
         }
-    }).collect();
-    */
+        utils::BuilderPattern::Owned => {
+            let body = body(&quote!(self));
+            quote!{
+                #vis fn #name(mut self, #args) -> Self {
+                    #body
+                    self
+                }
+            }
+        }
+        utils::BuilderPattern::Immutable => {
+            let body = body(&quote!(__builder));
+            quote!{
+                #vis fn #name(&self, #args) -> Self
+                where
+                    Self: ::core::clone::Clone,
+                {
+                    let mut __builder = ::core::clone::Clone::clone(self);
+                    #body
+                    __builder
+                }
+            }
+        }
+    }
+}
 
-    let derive = quote!{
-        pub struct #builder_ident {
-            #(#gen_builder_fields),*
+// `try_port` for a `#[builder(try_setter)]` field, shaped after the builder
+// pattern like the plain setter it forwards to.
+fn gen_pattern_try_setter(
+    field: &BuilderField,
+    value_ty: &syn::Type,
+    pattern: utils::BuilderPattern,
+    error_ident: &syn::Ident,
+    alloc: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let (receiver, ret, bounds) = match pattern {
+        utils::BuilderPattern::Mutable => (quote!(&mut self), quote!(&mut Self), quote!()),
+        utils::BuilderPattern::Owned => (quote!(self), quote!(Self), quote!()),
+        utils::BuilderPattern::Immutable => (quote!(&self), quote!(Self), quote!(Self: ::core::clone::Clone,)),
+    };
+    gen_try_setter(field, value_ty, error_ident, alloc, receiver, ret, bounds)
+}
+
+// Converts the argument with `TryInto` and hands it on to the plain setter. A
+// failed conversion is reported as the builder's own error, so the same `?`
+// covers both the setter and build().
+fn gen_try_setter(
+    field: &BuilderField,
+    value_ty: &syn::Type,
+    error_ident: &syn::Ident,
+    alloc: &proc_macro2::TokenStream,
+    receiver: proc_macro2::TokenStream,
+    ret: proc_macro2::TokenStream,
+    bounds: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if field.attrs.try_setter.is_none() {
+        return quote!();
+    }
+    let ident = &field.ident;
+    let setter_ident = &field.setter_ident;
+    let vis = field.setter_vis();
+    let try_ident = utils::prefixed_ident("try_", field.base_ident());
+    let invalid_msg = format!("invalid value for field `{}`: {{}}", utils::field_name_literal(ident));
+    // a plain setter that keeps the `Option` wants it back
+    let arg = if utils::is_field_optional(field.field) && field.attrs.setter.strip_option == Some(false) {
+        quote!(::core::option::Option::Some(#ident))
+    } else {
+        quote!(#ident)
+    };
+    quote!{
+        #vis fn #try_ident<__V>(#receiver, #ident: __V) -> ::core::result::Result<#ret, #error_ident>
+        where
+            __V: ::core::convert::TryInto<#value_ty>,
+            <__V as ::core::convert::TryInto<#value_ty>>::Error: ::core::fmt::Display,
+            #bounds
+        {
+            let #ident: #value_ty = match ::core::convert::TryInto::try_into(#ident) {
+                ::core::result::Result::Ok(value) => value,
+                ::core::result::Result::Err(err) => {
+                    return ::core::result::Result::Err(#error_ident::ValidationError(#alloc::format!(#invalid_msg, err)))
+                }
+            };
+            ::core::result::Result::Ok(self.#setter_ident(#arg))
         }
+    }
+}
 
-        impl #builder_ident {
-            #(#gen_setters)*
-            #gen_build
+// The setter generated by `setter` under the field's setter name, followed by a
+// deprecated copy for every `#[builder(alias = "...")]`.
+fn gen_with_aliases(
+    field: &BuilderField,
+    name: &syn::Ident,
+    setter: impl Fn(&syn::Ident) -> proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let note = format!("use `{}` instead", utils::field_name_literal(name));
+    let gen_aliases = field.attrs.alias.iter().map(|alias| {
+        let gen_alias = setter(alias);
+        quote!{
+            #[deprecated(note = #note)]
+            #gen_alias
         }
+    });
+    let gen_setter = setter(name);
+    quote!{
+        #gen_setter
+        #(#gen_aliases)*
+    }
+}
+
+// Builds the `#[builder(typestate)]` flavour of the builder. Setters take and
+// return the builder by value because setting a required field changes the
+// builder's type, and `build()` is only implemented once every type parameter
+// has moved from `()` to the field type.
+#[allow(clippy::too_many_arguments)]
+fn gen_typestate(
+    ast: &DeriveInput,
+    target: &BuilderTarget,
+    error_ident: &syn::Ident,
+    alloc: &proc_macro2::TokenStream,
+    typestate_params: &[syn::Ident],
+    typestate_set_types: &[&syn::Type],
+    gen_builder_attrs: &proc_macro2::TokenStream,
+    gen_builder_fields: &[proc_macro2::TokenStream],
+    gen_setters: &[proc_macro2::TokenStream],
+    build_fn: &syn::Ident,
+    gen_build_body: &proc_macro2::TokenStream,
+    gen_error: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let struct_name = &ast.ident;
+    let builder_ident = &target.builder_ident;
+    let builder_fn = &target.builder_fn;
+    let vis = &target.vis;
+    let fields = &target.fields;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let struct_args = utils::generic_args(&ast.generics);
+
+    // the struct's own generics followed by one parameter per required field
+    let mut decl_generics = ast.generics.clone();
+    let mut setter_generics = ast.generics.clone();
+    for param in typestate_params {
+        decl_generics.params.push(syn::parse_quote!(#param = ()));
+        setter_generics.params.push(syn::parse_quote!(#param));
+    }
+    let (setter_impl_generics, setter_ty_generics, _) = setter_generics.split_for_impl();
+
+    let slot_idents: Vec<_> = fields.iter().filter(|field| !field.is_skipped()).map(|field| &field.ident).collect();
+
+    let gen_builder_fn = match builder_fn {
+        Some(builder_fn) => quote!{
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                #vis fn #builder_fn() -> #builder_ident #ty_generics {
+                    <#builder_ident #ty_generics as ::core::default::Default>::default()
+                }
+            }
+        },
+        None => quote!(),
+    };
 
-        impl #struct_name {
-            pub fn builder() -> #builder_ident {
+    let gen_transitions: Vec<_> = fields.iter().filter(|field| field.is_required()).enumerate().map(|(index, field)| {
+        let ident = &field.ident;
+        let ty = &field.field.ty;
+        // the returned builder has this field's parameter replaced by the
+        // field type, every other parameter is carried over unchanged
+        let param = utils::typestate_param_ident(ident);
+        let next_params: Vec<_> = typestate_params.iter().map(|p| {
+            if *p == param { quote!(#ty) } else { quote!(#p) }
+        }).collect();
+        let moved_fields: Vec<_> = slot_idents.iter().filter(|f| **f != ident).map(|f| {
+            quote!(#f: self.#f)
+        }).collect();
+        let setter_vis = field.setter_vis();
+        let next_builder = quote!(#builder_ident<#(#struct_args,)* #(#next_params),*>);
+        let gen_transition_fn = |name: &syn::Ident, arg_ty: proc_macro2::TokenStream, value: proc_macro2::TokenStream| quote!{
+            #setter_vis fn #name(self, #ident: #arg_ty) -> #next_builder {
                 #builder_ident {
-                    #(#gen_builder_default),*
+                    #ident: #value,
+                    #(#moved_fields,)*
+                    __set: {
+                        let mut set = self.__set;
+                        set[#index] = true;
+                        set
+                    },
+                    __phantom: ::core::marker::PhantomData,
                 }
             }
+        };
+        // a hand-written setter can't rebuild the builder in its next state
+        // itself without knowing its hidden fields, so it forwards to
+        // `__set_user` instead
+        if field.attrs.setter.custom.is_some() {
+            let raw_ident = quote::format_ident!("__set_{}", ident);
+            let gen_raw_transition = gen_transition_fn(&raw_ident, quote!(#ty), quote!(#ident));
+            return quote!{
+                #[doc(hidden)]
+                #gen_raw_transition
+            };
         }
-    };
+        let (arg_ty, value) = field.setter_arg(ty, ident);
+        let gen_transition = gen_with_aliases(field, &field.setter_ident, |name| gen_transition_fn(name, arg_ty.clone(), value.clone()));
+        let gen_try_transition = gen_try_setter(field, ty, error_ident, alloc, quote!(self), next_builder, quote!());
+        quote!{
+            #gen_transition
+            #gen_try_transition
+        }
+    }).collect();
+
+    quote!{
+        #gen_builder_attrs
+        #vis struct #builder_ident #decl_generics #where_clause {
+            #(#gen_builder_fields,)*
+            // required slots are typestate parameters, so the struct's own
+            // generics are not necessarily used by any other field
+            __phantom: ::core::marker::PhantomData<fn() -> #struct_name #ty_generics>,
+        }
+
+        impl #setter_impl_generics #builder_ident #setter_ty_generics #where_clause {
+            #(#gen_setters)*
+            #(#gen_transitions)*
+        }
+
+        impl #impl_generics #builder_ident<#(#struct_args,)* #(#typestate_set_types),*> #where_clause {
+            #vis fn #build_fn(self) -> ::core::result::Result<#struct_name #ty_generics, #error_ident> {
+                #gen_build_body
+            }
+        }
+
+        #gen_error
 
-    Ok(derive.into())
+        #gen_builder_fn
+    }
 }
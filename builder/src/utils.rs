@@ -2,23 +2,6 @@
 
 use syn::{Result};
 
-pub fn derive_get_struct_fields(ast: &syn::DeriveInput) -> Option<&syn::punctuated::Punctuated<syn::Field, syn::Token![,]>>{
-    if let syn::Data::Struct(
-        syn::DataStruct{
-            fields: syn::Fields::Named(
-                syn::FieldsNamed{
-                    ref named,
-                    ..
-                }
-            ),
-            ..
-        }
-    ) = ast.data {
-       return Some(named)
-    }
-    None
-}
-
 pub fn is_field_optional(field: &syn::Field) -> bool{
     if let syn::Type::Path(
         syn::TypePath{
@@ -43,31 +26,6 @@ pub fn is_field_optional(field: &syn::Field) -> bool{
     return false
 }
 
-pub fn is_field(field: &syn::Field, name: String) -> bool{
-    if let syn::Type::Path(
-        syn::TypePath{
-            path:syn::Path{
-                ref segments,
-                ..
-            },
-            ..
-        }
-    ) = field.ty{
-        if let Some(
-            syn::PathSegment{
-                ident,
-                ..
-            }
-        ) = segments.last() {  // we need to check the lat one, so xxx::Option() can work
-            eprintln!("{:#?}", ident);
-            if ident == &name {
-                return true
-            }
-        }
-    }
-    return false
-}
-
 pub fn extract_inner_type(field: &syn::Field, container_ident: String) -> Option<&syn::Type>{
     if let syn::Type::Path(
         syn::TypePath{
@@ -103,37 +61,440 @@ pub fn extract_inner_type(field: &syn::Field, container_ident: String) -> Option
     return None
 }
 
-pub fn get_each_attr_name(field: &syn::Field) -> Option<Result<String>> {
-    if let Some(attr) = field.attrs.last() {
-        if let Ok(ref meta) = attr.parse_meta() {
-            if meta.path().is_ident("builder") {
-                if let syn::Meta::List(
-                    syn::MetaList{
-                        nested,
-                        ..
-                    }
-                ) = meta {
-                    if let Some(syn::NestedMeta::Meta(
-                        syn::Meta::NameValue(
-                            syn::MetaNameValue{
-                                path,
-                                lit:syn::Lit::Str(
-                                    lit
-                                ),
-                                ..
-                            }
-                        )
-                    )) = nested.last() {
-                        if path.is_ident("each") {
-                            return Some(Ok(lit.value()))
-                        } else {
-                            return Some(Err(syn::Error::new_spanned(meta, r#"expected `builder(each = "...")`"#)))
+// Item type(s) an `each` setter feeds into the collection through `Extend`:
+// `[K, V]` for map-like types (`HashMap`, `BTreeMap`, `indexmap::IndexMap`..),
+// the first type argument for anything else, `None` when there is none.
+pub fn extract_each_item_types(field: &syn::Field) -> Option<Vec<&syn::Type>> {
+    if let syn::Type::Path(syn::TypePath{ path: syn::Path{ ref segments, .. }, .. }) = field.ty {
+        if let Some(syn::PathSegment{ ident, arguments: syn::PathArguments::AngleBracketed(args) }) = segments.last() {
+            let types: Vec<_> = args.args.iter().filter_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }).collect();
+            if ident.to_string().ends_with("Map") && types.len() >= 2 {
+                return Some(types[..2].to_vec());
+            } else if let Some(ty) = types.first() {
+                return Some(vec![*ty]);
+            }
+        }
+    }
+    None
+}
+
+// The builder derived for a `#[builder(sub_builder)]` field's type, keeping
+// its path and generic arguments: `tls::Config<T>` gives `tls::ConfigBuilder<T>`.
+pub fn sub_builder_type(ty: &syn::Type) -> Option<syn::Type> {
+    if let syn::Type::Path(type_path) = ty {
+        if type_path.qself.is_none() {
+            let mut type_path = type_path.clone();
+            let segment = type_path.path.segments.last_mut()?;
+            segment.ident = quote::format_ident!("{}Builder", segment.ident);
+            return Some(syn::Type::Path(type_path));
+        }
+    }
+    None
+}
+
+// The error type of the builder from `sub_builder_type()`, `tls::ConfigBuilderError`.
+pub fn sub_builder_error_type(ty: &syn::Type) -> Option<syn::Path> {
+    if let syn::Type::Path(type_path) = ty {
+        if type_path.qself.is_none() {
+            let mut path = type_path.path.clone();
+            let segment = path.segments.last_mut()?;
+            segment.ident = quote::format_ident!("{}BuilderError", segment.ident);
+            segment.arguments = syn::PathArguments::None;
+            return Some(path);
+        }
+    }
+    None
+}
+
+// A type the way it would be written, `Vec<String>` rather than the spaced out
+// `Vec < String >` of its tokens.
+pub fn type_to_string(ty: &syn::Type) -> String {
+    let mut text = quote::quote!(#ty).to_string();
+    for (spaced, tight) in [(" <", "<"), ("< ", "<"), (" >", ">"), (" ::", "::"), (":: ", "::"), (" ,", ","), ("& ", "&")] {
+        text = text.replace(spaced, tight);
+    }
+    text
+}
+
+pub fn field_name_literal(ident: &syn::Ident) -> String {
+    // strip the `r#` so raw identifiers like `r#type` read as `type`
+    let name = ident.to_string();
+    name.trim_start_matches("r#").to_string()
+}
+
+// `prefix` followed by the field name, `r#type` with `with_` gives `with_type`
+// and tuple field `_0` with `clear_` gives `clear_0`
+pub fn prefixed_ident(prefix: &str, ident: &syn::Ident) -> syn::Ident {
+    if prefix.is_empty() {
+        return ident.clone();
+    }
+    let name = field_name_literal(ident);
+    let name = if prefix.ends_with('_') { name.trim_start_matches('_') } else { &name };
+    quote::format_ident!("{}{}", prefix, name, span = ident.span())
+}
+
+pub fn field_camel_name(ident: &syn::Ident) -> String {
+    field_name_literal(ident)
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+pub fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, ch) in name.trim_start_matches("r#").chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
+
+pub fn missing_variant_ident(ident: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("Missing{}", field_camel_name(ident), span = ident.span())
+}
+
+// `InvalidTls` carrying the error of the nested builder of field `tls`
+pub fn invalid_variant_ident(ident: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("Invalid{}", field_camel_name(ident), span = ident.span())
+}
+
+pub fn typestate_param_ident(ident: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("__{}", field_camel_name(ident), span = ident.span())
+}
+
+// `<'a, T: Bound, const N: usize>` -> [`'a`, `T`, `N`], for spelling out a
+// generic type when more arguments have to be appended after the struct's own
+pub fn generic_args(generics: &syn::Generics) -> Vec<proc_macro2::TokenStream> {
+    generics.params.iter().map(|param| match param {
+        syn::GenericParam::Lifetime(lt) => {
+            let lifetime = &lt.lifetime;
+            quote::quote!(#lifetime)
+        }
+        syn::GenericParam::Type(ty) => {
+            let ident = &ty.ident;
+            quote::quote!(#ident)
+        }
+        syn::GenericParam::Const(c) => {
+            let ident = &c.ident;
+            quote::quote!(#ident)
+        }
+    }).collect()
+}
+
+/// Options given as `#[builder(setter(...))]`, on a field or struct-wide.
+#[derive(Default)]
+pub struct SetterAttrs {
+    // take `impl Into<T>` instead of `T`
+    pub into: bool,
+    pub vis: Option<syn::Visibility>,
+    // `with_` turns the `timeout` setter into `with_timeout`
+    pub prefix: Option<String>,
+    // field-level only, replaces the setter's name outright
+    pub name: Option<syn::Ident>,
+    // `strip_option = false`: setters of `Option<T>` fields take the `Option<T>`
+    pub strip_option: Option<bool>,
+    // field-level only, the setters are written by hand
+    pub custom: Option<syn::Path>,
+}
+
+fn parse_setter_attrs(list: &syn::MetaList, setter_attrs: &mut SetterAttrs) -> Result<()> {
+    for nested_meta in &list.nested {
+        match nested_meta {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("into") => {
+                setter_attrs.into = true;
+            }
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("custom") => {
+                setter_attrs.custom = Some(path.clone());
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Str(lit), .. })) if path.is_ident("vis") => {
+                setter_attrs.vis = Some(lit.parse()?);
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Str(lit), .. })) if path.is_ident("prefix") => {
+                setter_attrs.prefix = Some(lit.value());
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Str(lit), .. })) if path.is_ident("name") => {
+                setter_attrs.name = Some(lit.parse()?);
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Bool(lit), .. })) if path.is_ident("strip_option") => {
+                setter_attrs.strip_option = Some(lit.value);
+            }
+            _ => return Err(syn::Error::new_spanned(nested_meta, "unrecognized setter option")),
+        }
+    }
+    Ok(())
+}
+
+/// How setters and `build()` receive the builder, `#[builder(pattern = "...")]`.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuilderPattern {
+    // `&mut self -> &mut Self`
+    #[default]
+    Mutable,
+    // `self -> Self`, build() moves the fields out without cloning
+    Owned,
+    // `&self -> Self`, every setter returns an updated copy
+    Immutable,
+}
+
+/// How `merge()` combines two `each` collections, `#[builder(merge = "...")]`.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    // the other builder's items are added to ours
+    #[default]
+    Append,
+    // the other builder's items replace ours, unless it has none
+    Replace,
+}
+
+/// Options given as `#[builder(build_fn(...))]` on the struct.
+#[derive(Default)]
+pub struct BuildFnAttrs {
+    // mutable pattern only: build() takes the values out of the builder
+    // instead of cloning them
+    pub take: bool,
+    // `fn(&Builder) -> Result<(), E>` run before the value is assembled
+    pub validate: Option<syn::Path>,
+    // rename `build()`
+    pub name: Option<syn::Ident>,
+}
+
+fn parse_build_fn_attrs(list: &syn::MetaList, build_fn_attrs: &mut BuildFnAttrs) -> Result<()> {
+    for nested_meta in &list.nested {
+        match nested_meta {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("take") => {
+                build_fn_attrs.take = true;
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Str(lit), .. })) if path.is_ident("validate") => {
+                build_fn_attrs.validate = Some(lit.parse()?);
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Str(lit), .. })) if path.is_ident("name") => {
+                build_fn_attrs.name = Some(lit.parse()?);
+            }
+            _ => return Err(syn::Error::new_spanned(nested_meta, "unrecognized build_fn option")),
+        }
+    }
+    Ok(())
+}
+
+/// Options given as `#[builder(...)]` on the struct itself.
+#[derive(Default)]
+pub struct StructAttrs {
+    pub typestate: bool,
+    // generate `::core`/`::alloc` paths only, for no_std crates
+    pub no_std: bool,
+    // `#[builder(default)]`: fields left unset are taken from `Default::default()`
+    // of the struct itself, also for `Option` and `each` fields
+    pub default: Option<syn::Path>,
+    pub setter: SetterAttrs,
+    pub pattern: BuilderPattern,
+    pub build_fn: BuildFnAttrs,
+    // visibility of the generated builder, defaults to the struct's own
+    pub vis: Option<syn::Visibility>,
+    // rename the builder type and its `builder()` constructor
+    pub name: Option<syn::Ident>,
+    pub constructor: Option<syn::Ident>,
+    // `constructor = false`: don't generate the constructor at all
+    pub no_constructor: bool,
+    // `derive(...)` and `struct_attr(...)` forwarded to the builder struct
+    pub derive: Vec<syn::Path>,
+    pub struct_attr: Vec<syn::Meta>,
+}
+
+// Whether `derive(...)` lists the trait `name`, however its path is spelled:
+// `Default`, `std::default::Default` or `::core::default::Default`.
+pub fn derives_trait(derive: &[syn::Path], name: &str) -> bool {
+    derive.iter().any(|path| path.segments.last().is_some_and(|segment| segment.ident == name))
+}
+
+// The metas inside `key(...)`, which are forwarded as `#[meta]` attributes.
+fn get_nested_metas(list: &syn::MetaList) -> Result<Vec<syn::Meta>> {
+    list.nested.iter().map(|nested_meta| match nested_meta {
+        syn::NestedMeta::Meta(meta) => Ok(meta.clone()),
+        syn::NestedMeta::Lit(lit) => Err(syn::Error::new_spanned(lit, "expected an attribute")),
+    }).collect()
+}
+
+pub fn get_struct_attrs(ast: &syn::DeriveInput) -> Result<StructAttrs> {
+    let mut struct_attrs = StructAttrs::default();
+    // kept around to point errors about conflicting options at them
+    let mut pattern = None;
+    let mut build_fn_take = None;
+    for attr in ast.attrs.iter().filter(|attr| attr.path.is_ident("builder")) {
+        let meta = attr.parse_meta()?;
+        let nested = match meta {
+            syn::Meta::List(syn::MetaList{ nested, .. }) => nested,
+            _ => return Err(syn::Error::new_spanned(meta, "expected `builder(...)`")),
+        };
+        for nested_meta in nested {
+            match nested_meta {
+                syn::NestedMeta::Meta(syn::Meta::Path(ref path)) if path.is_ident("typestate") => {
+                    struct_attrs.typestate = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(ref path)) if path.is_ident("no_std") => {
+                    struct_attrs.no_std = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("default") => {
+                    struct_attrs.default = Some(path);
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(ref list)) if list.path.is_ident("setter") => {
+                    parse_setter_attrs(list, &mut struct_attrs.setter)?;
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(ref list)) if list.path.is_ident("derive") => {
+                    for meta in get_nested_metas(list)? {
+                        match meta {
+                            syn::Meta::Path(path) => struct_attrs.derive.push(path),
+                            _ => return Err(syn::Error::new_spanned(meta, "expected a trait to derive")),
                         }
-                        
                     }
                 }
+                syn::NestedMeta::Meta(syn::Meta::List(ref list)) if list.path.is_ident("struct_attr") => {
+                    struct_attrs.struct_attr.extend(get_nested_metas(list)?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(ref list)) if list.path.is_ident("build_fn") => {
+                    parse_build_fn_attrs(list, &mut struct_attrs.build_fn)?;
+                    if struct_attrs.build_fn.take {
+                        build_fn_take = Some(list.clone());
+                    }
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ ref path, lit: syn::Lit::Str(ref lit), .. })) if path.is_ident("pattern") => {
+                    struct_attrs.pattern = match lit.value().as_str() {
+                        "mutable" => BuilderPattern::Mutable,
+                        "owned" => BuilderPattern::Owned,
+                        "immutable" => BuilderPattern::Immutable,
+                        _ => return Err(syn::Error::new_spanned(lit, r#"expected "owned", "mutable" or "immutable""#)),
+                    };
+                    pattern = Some(lit.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ ref path, lit: syn::Lit::Str(ref lit), .. })) if path.is_ident("vis") => {
+                    struct_attrs.vis = Some(lit.parse()?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ ref path, lit: syn::Lit::Str(ref lit), .. })) if path.is_ident("name") => {
+                    struct_attrs.name = Some(lit.parse()?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ ref path, ref lit, .. })) if path.is_ident("constructor") => {
+                    match lit {
+                        syn::Lit::Str(lit) => struct_attrs.constructor = Some(lit.parse()?),
+                        syn::Lit::Bool(lit) if !lit.value => struct_attrs.no_constructor = true,
+                        _ => return Err(syn::Error::new_spanned(lit, r#"expected `constructor = "..."` or `constructor = false`"#)),
+                    }
+                }
+                _ => return Err(syn::Error::new_spanned(nested_meta, "unrecognized struct-level builder attribute")),
             }
         }
     }
-    None
-}
\ No newline at end of file
+    if let Some(name) = &struct_attrs.setter.name {
+        return Err(syn::Error::new_spanned(name, "`setter(name = \"...\")` only applies to fields"));
+    }
+    if let Some(custom) = &struct_attrs.setter.custom {
+        return Err(syn::Error::new_spanned(custom, "`setter(custom)` only applies to fields"));
+    }
+    if let Some(lit) = &pattern {
+        if struct_attrs.typestate && struct_attrs.pattern != BuilderPattern::Owned {
+            return Err(syn::Error::new_spanned(lit, "a typestate builder always uses the owned pattern"));
+        }
+    }
+    if let Some(list) = &build_fn_take {
+        if struct_attrs.pattern != BuilderPattern::Mutable || struct_attrs.typestate {
+            return Err(syn::Error::new_spanned(list, "`build_fn(take)` only applies to the mutable pattern"));
+        }
+    }
+    Ok(struct_attrs)
+}
+
+/// Options given as `#[builder(...)]` on a single field.
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub each: Option<syn::Ident>,
+    pub name: Option<syn::Ident>,
+    // value used by build() when the field was never set
+    pub default: Option<syn::Expr>,
+    pub setter: SetterAttrs,
+    // `fn(&T) -> Result<(), E>` run on the field's final value in build()
+    pub validate: Option<syn::Path>,
+    // forwarded to the field's slot in the builder struct
+    pub field_attr: Vec<syn::Meta>,
+    // no slot and no setter, build() fills the field with this expression
+    pub skip: Option<syn::Expr>,
+    // old setter names kept around as deprecated copies of the setter
+    pub alias: Vec<syn::Ident>,
+    // `try_field` setter converting its argument with `TryInto`
+    pub try_setter: Option<syn::Path>,
+    // the field's type derives Builder too and gets a nested builder as slot
+    pub sub_builder: Option<syn::Path>,
+    // `each` fields only, kept as the literal to point errors at
+    pub merge: Option<(MergeMode, syn::LitStr)>,
+}
+
+pub fn get_field_attrs(field: &syn::Field) -> Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
+    for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("builder")) {
+        let meta = attr.parse_meta()?;
+        let unrecognized = || syn::Error::new_spanned(&meta, r#"expected `builder(each = "...")`"#);
+        let nested = match meta {
+            syn::Meta::List(syn::MetaList{ ref nested, .. }) => nested,
+            _ => return Err(unrecognized()),
+        };
+        for nested_meta in nested {
+            match nested_meta {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue{ path, lit: syn::Lit::Str(lit), .. })) => {
+                    if path.is_ident("each") {
+                        field_attrs.each = Some(lit.parse()?);
+                    } else if path.is_ident("name") {
+                        field_attrs.name = Some(lit.parse()?);
+                    } else if path.is_ident("default") {
+                        field_attrs.default = Some(lit.parse()?);
+                    } else if path.is_ident("validate") {
+                        field_attrs.validate = Some(lit.parse()?);
+                    } else if path.is_ident("skip") {
+                        field_attrs.skip = Some(lit.parse()?);
+                    } else if path.is_ident("alias") {
+                        field_attrs.alias.push(lit.parse()?);
+                    } else if path.is_ident("merge") {
+                        let mode = match lit.value().as_str() {
+                            "append" => MergeMode::Append,
+                            "replace" => MergeMode::Replace,
+                            _ => return Err(syn::Error::new_spanned(lit, r#"expected "append" or "replace""#)),
+                        };
+                        field_attrs.merge = Some((mode, lit.clone()));
+                    } else {
+                        return Err(unrecognized());
+                    }
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("default") => {
+                    field_attrs.default = Some(syn::parse_quote!(::core::default::Default::default()));
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("skip") => {
+                    field_attrs.skip = Some(syn::parse_quote!(::core::default::Default::default()));
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("try_setter") => {
+                    field_attrs.try_setter = Some(path.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("sub_builder") => {
+                    field_attrs.sub_builder = Some(path.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(list)) if list.path.is_ident("setter") => {
+                    parse_setter_attrs(list, &mut field_attrs.setter)?;
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(list)) if list.path.is_ident("field_attr") => {
+                    field_attrs.field_attr.extend(get_nested_metas(list)?);
+                }
+                _ => return Err(unrecognized()),
+            }
+        }
+    }
+    Ok(field_attrs)
+}
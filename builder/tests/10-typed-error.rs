@@ -0,0 +1,35 @@
+// The error returned by build() is a generated enum with one variant per
+// required field, so callers can match on exactly which field was missing
+// instead of parsing an error string.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: String,
+    env: Option<Vec<String>>,
+}
+
+fn main() {
+    let err = Command::builder()
+        .executable("cargo".to_owned())
+        .build()
+        .err()
+        .unwrap();
+
+    assert_eq!(err, CommandBuilderError::MissingCurrentDir);
+    assert_eq!(err.to_string(), "Field `current_dir` is missing");
+
+    let boxed: Box<dyn std::error::Error> = Box::new(err);
+    let _ = boxed;
+
+    match Command::builder().build() {
+        Err(CommandBuilderError::MissingExecutable) => {}
+        Err(CommandBuilderError::MissingCurrentDir) => unreachable!(),
+        Err(CommandBuilderError::ValidationError(_)) => unreachable!(),
+        Ok(_) => unreachable!(),
+    }
+}
@@ -0,0 +1,34 @@
+// With #[builder(typestate)] the builder tracks which required fields have
+// been set in its type, so build() only exists once all of them are present.
+// Optional and repeated fields can be set at any point along the chain.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+    timeout: u64,
+}
+
+fn main() {
+    let command = Command::builder()
+        .arg("build".to_owned())
+        .timeout(30)
+        .current_dir("..".to_owned())
+        .executable("cargo".to_owned())
+        .arg("--release".to_owned())
+        .build()
+        .unwrap();
+
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.args, vec!["build", "--release"]);
+    assert_eq!(command.current_dir.as_deref(), Some(".."));
+    assert_eq!(command.timeout, 30);
+
+    let unset: CommandBuilder = Command::builder();
+    let _: CommandBuilder<String, ()> = unset.executable("cargo".to_owned());
+}
@@ -0,0 +1,17 @@
+// Forgetting a required field on a typestate builder is a compile error
+// instead of a runtime "Field is missing" error.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Command {
+    executable: String,
+    timeout: u64,
+}
+
+fn main() {
+    let _ = Command::builder()
+        .executable("cargo".to_owned())
+        .build();
+}
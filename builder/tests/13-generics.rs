@@ -0,0 +1,67 @@
+// Type parameters, lifetimes, const generics and where-clauses on the input
+// struct carry over to the builder, its impl block and builder().
+
+use derive_builder::Builder;
+use std::fmt::Debug;
+
+pub trait Body: Clone {
+    fn len(&self) -> usize;
+}
+
+impl Body for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+#[derive(Builder)]
+pub struct Request<'a, T: Body, const N: usize, U = u8>
+where
+    U: Clone + Debug,
+{
+    url: &'a str,
+    body: T,
+    headers: [U; N],
+    #[builder(each = "tag")]
+    tags: Vec<&'a str>,
+    referrer: Option<&'a str>,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Response<'a, T>
+where
+    T: Debug,
+{
+    status: u16,
+    body: &'a T,
+    reason: Option<String>,
+}
+
+fn main() {
+    let url = String::from("https://example.com");
+    let request: Request<Vec<u8>, 2> = Request::builder()
+        .url(&url)
+        .body(vec![1, 2, 3])
+        .headers([0, 1])
+        .tag("a")
+        .build()
+        .unwrap();
+
+    assert_eq!(request.url, "https://example.com");
+    assert_eq!(request.body.len(), 3);
+    assert_eq!(request.headers, [0u8, 1]);
+    assert_eq!(request.tags, vec!["a"]);
+    assert!(request.referrer.is_none());
+
+    let body = vec!["x"];
+    let response = Response::builder()
+        .body(&body)
+        .status(200)
+        .build()
+        .unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body.len(), 1);
+    assert!(response.reason.is_none());
+}
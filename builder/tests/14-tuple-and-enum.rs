@@ -0,0 +1,56 @@
+// Tuple structs get positional `_0`, `_1`.. setters unless a field is named
+// with #[builder(name = "...")], and enums get one builder per variant which
+// is obtained from `Shape::<variant>_builder()`.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Point(i32, #[builder(name = "y")] i32, Option<String>);
+
+#[derive(Debug, PartialEq, Builder)]
+pub enum Shape {
+    Circle(#[builder(name = "radius")] f64),
+    Rect {
+        width: f64,
+        height: f64,
+        label: Option<String>,
+    },
+    RoundedRect(f64, f64),
+    Empty,
+}
+
+// a variant's builder carries all of the enum's generics, used or not
+#[derive(Debug, PartialEq, Builder)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+fn main() {
+    let point = Point::builder()._0(1).y(2).build().unwrap();
+    assert_eq!(point.0, 1);
+    assert_eq!(point.1, 2);
+    assert!(point.2.is_none());
+
+    let err = Point::builder()._0(1).build().err().unwrap();
+    assert_eq!(err, PointBuilderError::MissingY);
+
+    let circle = Shape::circle_builder().radius(1.5).build().unwrap();
+    assert_eq!(circle, Shape::Circle(1.5));
+
+    let rect = Shape::rect_builder().width(2.0).height(3.0).build().unwrap();
+    assert_eq!(rect, Shape::Rect { width: 2.0, height: 3.0, label: None });
+
+    let rounded = Shape::rounded_rect_builder()._0(1.0)._1(2.0).build().unwrap();
+    assert_eq!(rounded, Shape::RoundedRect(1.0, 2.0));
+
+    let err = Shape::rect_builder().width(2.0).build().err().unwrap();
+    assert_eq!(err, ShapeRectBuilderError::MissingHeight);
+
+    assert_eq!(Shape::empty_builder().build().unwrap(), Shape::Empty);
+
+    let left: Either<i32, String> = Either::left_builder()._0(1).build().unwrap();
+    assert_eq!(left, Either::Left(1));
+    let right = Either::<i32, String>::right_builder()._0("r".to_owned()).build().unwrap();
+    assert_eq!(right, Either::Right("r".to_owned()));
+}
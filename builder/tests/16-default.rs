@@ -0,0 +1,107 @@
+// Fields marked #[builder(default)] fall back to Default::default() when they
+// are never set, and #[builder(default = "...")] to an arbitrary expression
+// which may refer to fields declared before it. A struct-level
+// #[builder(default)] takes every unset field from the struct's own Default,
+// including options and `each` collections. Only fields that were never set
+// fall back: an explicit `None` or empty collection is kept.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(default)]
+    args: Vec<String>,
+    #[builder(default = "format!(\"{}.log\", executable)")]
+    log_file: String,
+    #[builder(default = "Some(30)")]
+    timeout: Option<u64>,
+}
+
+#[derive(Builder)]
+#[builder(default)]
+pub struct Limits {
+    memory: u64,
+    #[builder(default = "memory / 2")]
+    swap: u64,
+    cpus: u32,
+    timeout: Option<u64>,
+    #[builder(each = "tag")]
+    tags: Vec<String>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            memory: 1024,
+            swap: 0,
+            cpus: 4,
+            timeout: Some(30),
+            tags: vec!["x".to_owned()],
+        }
+    }
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    host: String,
+    #[builder(default = "8080")]
+    port: u16,
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .build()
+        .unwrap();
+    assert!(command.args.is_empty());
+    assert_eq!(command.log_file, "cargo.log");
+    assert_eq!(command.timeout, Some(30));
+
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .args(vec!["build".to_owned()])
+        .log_file("build.log".to_owned())
+        .timeout(5)
+        .build()
+        .unwrap();
+    assert_eq!(command.args, vec!["build"]);
+    assert_eq!(command.log_file, "build.log");
+    assert_eq!(command.timeout, Some(5));
+
+    let limits = Limits::builder().cpus(8).build().unwrap();
+    assert_eq!(limits.memory, 1024);
+    assert_eq!(limits.swap, 512);
+    assert_eq!(limits.cpus, 8);
+    assert_eq!(limits.timeout, Some(30));
+    assert_eq!(limits.tags, vec!["x"]);
+
+    let limits = Limits::builder().timeout(5).tag("y".to_owned()).build().unwrap();
+    assert_eq!(limits.timeout, Some(5));
+    assert_eq!(limits.tags, vec!["y"]);
+
+    let limits = Limits::builder().timeout_opt(None).tags(Vec::new()).build().unwrap();
+    assert_eq!(limits.timeout, None);
+    assert!(limits.tags.is_empty());
+
+    // clearing unsets the field again, so the default applies
+    let limits = Limits::builder().timeout(5).clear_timeout().build().unwrap();
+    assert_eq!(limits.timeout, Some(30));
+
+    // merging in an explicit `None` overrides the value set before
+    let mut cli = Limits::builder();
+    cli.timeout_opt(None);
+    let limits = Limits::builder().timeout(5).merge(cli).build().unwrap();
+    assert_eq!(limits.timeout, None);
+
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .timeout_opt(None)
+        .build()
+        .unwrap();
+    assert_eq!(command.timeout, None);
+
+    let server = Server::builder().host("localhost".to_owned()).build().unwrap();
+    assert_eq!(server.port, 8080);
+}
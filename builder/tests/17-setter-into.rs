@@ -0,0 +1,44 @@
+// With #[builder(setter(into))] a setter accepts anything convertible into
+// the field type, so callers don't need `.to_owned()` everywhere. It can be
+// given per field or once on the struct, and also applies to the element of
+// Option<T> setters and `each` setters.
+
+use derive_builder::Builder;
+use std::path::PathBuf;
+
+#[derive(Builder)]
+pub struct Command {
+    #[builder(setter(into))]
+    executable: String,
+    #[builder(each = "arg", setter(into))]
+    args: Vec<String>,
+    #[builder(setter(into))]
+    current_dir: Option<PathBuf>,
+    timeout: u64,
+}
+
+#[derive(Builder)]
+#[builder(setter(into), typestate)]
+pub struct Server {
+    host: String,
+    alias: Option<String>,
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo")
+        .arg("build")
+        .arg(String::from("--release"))
+        .current_dir("/tmp")
+        .timeout(30)
+        .build()
+        .unwrap();
+
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.args, vec!["build", "--release"]);
+    assert_eq!(command.current_dir, Some(PathBuf::from("/tmp")));
+
+    let server = Server::builder().host("localhost").alias("lo").build().unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.alias.as_deref(), Some("lo"));
+}
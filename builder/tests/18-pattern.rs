@@ -0,0 +1,58 @@
+// #[builder(pattern = "...")] picks how setters and build() receive the
+// builder. The owned pattern moves the fields out in build(), so none of the
+// field types have to implement Clone. The mutable pattern can be asked to
+// take the values out of the builder with #[builder(build_fn(take))].
+
+use derive_builder::Builder;
+
+// Deliberately not Clone.
+#[derive(Debug, PartialEq)]
+pub struct Payload(Vec<u8>);
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Owned {
+    payload: Payload,
+    #[builder(each = "chunk")]
+    chunks: Vec<Payload>,
+    name: Option<String>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "mutable", build_fn(take))]
+pub struct Taken {
+    payload: Payload,
+    retries: Option<u8>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Immutable {
+    host: String,
+    port: u16,
+}
+
+fn main() {
+    let owned = Owned::builder()
+        .payload(Payload(vec![1]))
+        .chunk(Payload(vec![2]))
+        .name("x".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(owned.payload, Payload(vec![1]));
+    assert_eq!(owned.chunks, vec![Payload(vec![2])]);
+
+    let mut builder = Taken::builder();
+    assert_eq!(builder.retries(3).build().err(), Some(TakenBuilderError::MissingPayload));
+    let taken = builder.payload(Payload(vec![3])).build().unwrap();
+    assert_eq!(taken.payload, Payload(vec![3]));
+    assert_eq!(taken.retries, Some(3));
+    // everything was taken out by the first successful build()
+    assert_eq!(builder.build().err(), Some(TakenBuilderError::MissingPayload));
+
+    let base = Immutable::builder().host("localhost".to_owned());
+    let a = base.port(80).build().unwrap();
+    let b = base.port(443).build().unwrap();
+    assert_eq!((a.host.as_str(), a.port), ("localhost", 80));
+    assert_eq!((b.host.as_str(), b.port), ("localhost", 443));
+}
@@ -0,0 +1,58 @@
+// #[builder(build_fn(validate = "..."))] runs a check on the staged builder
+// before build() assembles the struct, and #[builder(validate = "...")] runs
+// a check on a single field's value. Errors are converted into the builder's
+// error type, so a `String` error becomes its `ValidationError` variant.
+
+use derive_builder::Builder;
+
+#[derive(Debug, Builder)]
+#[builder(build_fn(validate = "Self::check"))]
+pub struct Server {
+    host: String,
+    #[builder(validate = "check_port")]
+    port: u16,
+    tls: Option<bool>,
+    plain_only: Option<bool>,
+}
+
+fn check_port(port: &u16) -> Result<(), String> {
+    if *port < 1024 {
+        return Err(format!("port {} is privileged", port));
+    }
+    Ok(())
+}
+
+impl ServerBuilder {
+    fn check(&self) -> Result<(), String> {
+        if self.tls == Some(true) && self.plain_only == Some(true) {
+            return Err("tls and plain_only are mutually exclusive".to_owned());
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let server = Server::builder()
+        .host("localhost".to_owned())
+        .port(8080)
+        .tls(true)
+        .build()
+        .unwrap();
+    assert_eq!(server.port, 8080);
+
+    let err = Server::builder()
+        .host("localhost".to_owned())
+        .port(80)
+        .build()
+        .unwrap_err();
+    assert_eq!(err, ServerBuilderError::ValidationError("port 80 is privileged".to_owned()));
+
+    let err = Server::builder()
+        .host("localhost".to_owned())
+        .port(8080)
+        .tls(true)
+        .plain_only(true)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "tls and plain_only are mutually exclusive");
+}
@@ -0,0 +1,41 @@
+// `each` works with any collection implementing Default + Extend. Map-like
+// types get a setter taking a key and a value, sets keep their usual insert
+// semantics.
+
+use derive_builder::Builder;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+#[derive(Builder)]
+pub struct Request {
+    #[builder(each = "header", setter(into))]
+    headers: HashMap<String, String>,
+    #[builder(each = "param")]
+    params: BTreeMap<&'static str, u32>,
+    #[builder(each = "tag")]
+    tags: HashSet<&'static str>,
+    #[builder(each = "flag")]
+    flags: BTreeSet<char>,
+    #[builder(each = "step")]
+    steps: VecDeque<u8>,
+}
+
+fn main() {
+    let request = Request::builder()
+        .header("accept", "*/*")
+        .header("host", "example.com")
+        .param("page", 2)
+        .tag("a")
+        .tag("a")
+        .flag('v')
+        .step(1)
+        .step(2)
+        .build()
+        .unwrap();
+
+    assert_eq!(request.headers.len(), 2);
+    assert_eq!(request.headers["host"], "example.com");
+    assert_eq!(request.params.get("page"), Some(&2));
+    assert_eq!(request.tags.len(), 1);
+    assert!(request.flags.contains(&'v'));
+    assert_eq!(request.steps, VecDeque::from(vec![1, 2]));
+}
@@ -0,0 +1,15 @@
+// When the element type of an `each` collection can't be read off the field
+// type, the macro reports it on the type instead of generating a broken
+// setter.
+
+use derive_builder::Builder;
+
+type Args = Vec<String>;
+
+#[derive(Builder)]
+pub struct Command {
+    #[builder(each = "arg")]
+    args: Args,
+}
+
+fn main() {}
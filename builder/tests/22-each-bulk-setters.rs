@@ -0,0 +1,35 @@
+// Alongside the single element `each` setter, a field gets a setter that
+// replaces the whole collection and an `extend_` setter appending to it. If
+// `each` reuses the field's own name there is only the element setter and the
+// extend setter.
+
+use derive_builder::Builder;
+use std::collections::HashMap;
+
+#[derive(Builder)]
+pub struct Command {
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    #[builder(each = "env")]
+    env: HashMap<String, String>,
+    #[builder(each = "path")]
+    path: Vec<String>,
+}
+
+fn main() {
+    let command = Command::builder()
+        .arg("ignored".to_owned())
+        .args(vec!["build".to_owned()])
+        .arg("--release".to_owned())
+        .extend_args(["-p", "foo"].iter().map(|s| s.to_string()))
+        .env("A".to_owned(), "1".to_owned())
+        .extend_env(vec![("B".to_owned(), "2".to_owned())])
+        .path("/bin".to_owned())
+        .extend_path(vec!["/usr/bin".to_owned()])
+        .build()
+        .unwrap();
+
+    assert_eq!(command.args, vec!["build", "--release", "-p", "foo"]);
+    assert_eq!(command.env.len(), 2);
+    assert_eq!(command.path, vec!["/bin", "/usr/bin"]);
+}
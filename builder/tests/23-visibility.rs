@@ -0,0 +1,41 @@
+// The builder, its setters and build() take the visibility of the struct, so
+// a builder derived inside a module can be used from outside of it.
+// #[builder(vis = "...")] overrides that for the whole builder and
+// #[builder(setter(vis = "..."))] for a single setter.
+
+mod config {
+    use derive_builder::Builder;
+
+    #[derive(Builder)]
+    pub struct Command {
+        pub executable: String,
+        #[builder(each = "arg")]
+        pub args: Vec<String>,
+        #[builder(setter(vis = ""))]
+        pub secret: Option<String>,
+    }
+
+    #[derive(Builder)]
+    #[builder(vis = "pub(crate)")]
+    pub struct Server {
+        pub port: u16,
+    }
+
+    pub fn with_secret(builder: &mut CommandBuilder) {
+        builder.secret("hunter2".to_owned());
+    }
+}
+
+use config::{Command, Server};
+
+fn main() {
+    let mut builder = Command::builder();
+    builder.executable("cargo".to_owned()).arg("build".to_owned());
+    config::with_secret(&mut builder);
+    let command = builder.build().unwrap();
+    assert_eq!(command.args, vec!["build"]);
+    assert_eq!(command.secret.as_deref(), Some("hunter2"));
+
+    let server: config::ServerBuilder = Server::builder();
+    let _ = server;
+}
@@ -0,0 +1,17 @@
+// A setter made private with #[builder(setter(vis = ""))] can't be called
+// from outside the module defining the struct.
+
+mod config {
+    use derive_builder::Builder;
+
+    #[derive(Builder)]
+    pub struct Command {
+        pub executable: String,
+        #[builder(setter(vis = ""))]
+        pub secret: Option<String>,
+    }
+}
+
+fn main() {
+    config::Command::builder().secret("hunter2".to_owned());
+}
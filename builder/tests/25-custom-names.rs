@@ -0,0 +1,43 @@
+// The builder type, the constructor on the struct and the build method can
+// all be renamed, and `constructor = false` leaves the constructor out so it
+// doesn't collide with an existing `builder()` method.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(name = "CmdSpec", constructor = "spec", build_fn(name = "finish"))]
+pub struct Command {
+    executable: String,
+}
+
+impl Command {
+    pub fn builder() -> &'static str {
+        "hand written"
+    }
+}
+
+#[derive(Builder)]
+#[builder(constructor = false)]
+pub struct Server {
+    port: u16,
+}
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder { port: Some(8080), ..ServerBuilder::new() }
+    }
+}
+
+fn main() {
+    let mut spec: CmdSpec = Command::spec();
+    let command = spec.executable("cargo".to_owned()).finish().unwrap();
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(Command::builder(), "hand written");
+
+    match Command::spec().finish() {
+        Err(CmdSpecError::MissingExecutable) => {}
+        _ => unreachable!(),
+    }
+
+    assert_eq!(Server::builder().build().unwrap().port, 8080);
+}
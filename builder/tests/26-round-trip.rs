@@ -0,0 +1,58 @@
+// An existing value can be turned back into its builder, either by value
+// through `From` or by cloning through `to_builder()`, to rebuild it with a
+// couple of fields changed.
+
+use derive_builder::Builder;
+
+#[derive(Clone, Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+    #[builder(default)]
+    timeout: u64,
+}
+
+#[derive(Builder)]
+pub struct Point(i32, i32);
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .arg("build".to_owned())
+        .current_dir("..".to_owned())
+        .build()
+        .unwrap();
+
+    let release = command
+        .to_builder()
+        .arg("--release".to_owned())
+        .timeout(60)
+        .build()
+        .unwrap();
+    assert_eq!(release.executable, "cargo");
+    assert_eq!(release.args, vec!["build", "--release"]);
+    assert_eq!(release.current_dir.as_deref(), Some(".."));
+    assert_eq!(release.timeout, 60);
+    assert_eq!(command.args, vec!["build"]);
+
+    let mut builder = CommandBuilder::from(command);
+    let test = builder.args(vec!["test".to_owned()]).build().unwrap();
+    assert_eq!(test.args, vec!["test"]);
+
+    let point = Point::builder()._0(1)._1(2).build().unwrap();
+    let moved = PointBuilder::from(point)._1(5).build().unwrap();
+    assert_eq!((moved.0, moved.1), (1, 5));
+
+    let server = Server::builder().host("localhost".to_owned()).port(80).build().unwrap();
+    let server = ServerBuilder::from(server).port(8080).build().unwrap();
+    assert_eq!(server.port, 8080);
+}
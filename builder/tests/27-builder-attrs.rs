@@ -0,0 +1,53 @@
+// #[builder(derive(...))] and #[builder(struct_attr(...))] are forwarded to
+// the generated builder struct and #[builder(field_attr(...))] to the slot of
+// a single field, e.g. to log a half-built builder or clone it into variants.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(derive(Debug, Clone, PartialEq), struct_attr(must_use))]
+pub struct Command {
+    executable: String,
+    #[builder(field_attr(allow(dead_code)))]
+    current_dir: Option<String>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable", derive(Clone))]
+pub struct Server {
+    port: u16,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable", derive(core::clone::Clone))]
+pub struct Client {
+    retries: u8,
+}
+
+#[derive(Builder)]
+#[builder(typestate, derive(Debug))]
+pub struct Token {
+    value: String,
+}
+
+fn main() {
+    let mut builder = Command::builder();
+    builder.executable("cargo".to_owned());
+
+    let mut other = builder.clone();
+    other.current_dir("..".to_owned());
+    assert_ne!(builder, other);
+
+    let debug = format!("{:?}", builder);
+    assert!(debug.starts_with("CommandBuilder {"));
+    assert!(debug.contains("\"cargo\""));
+
+    let server = Server::builder().port(80).build().unwrap();
+    assert_eq!(server.port, 80);
+
+    let client = Client::builder().retries(3).build().unwrap();
+    assert_eq!(client.retries, 3);
+
+    let token = Token::builder().value("x".to_owned());
+    assert!(format!("{:?}", token).contains("\"x\""));
+}
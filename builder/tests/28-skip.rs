@@ -0,0 +1,76 @@
+// Fields marked #[builder(skip)] get neither a setter nor a slot in the
+// builder. build() fills them with Default::default(), or with the expression
+// given as #[builder(skip = "...")], which may refer to fields declared before
+// it.
+
+use derive_builder::Builder;
+use std::marker::PhantomData;
+
+#[derive(Builder, Clone)]
+pub struct Command {
+    executable: String,
+    #[builder(skip)]
+    runs: u32,
+    #[builder(skip = "format!(\"{}.cache\", executable)")]
+    cache_file: String,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Handle<T> {
+    id: u64,
+    #[builder(skip)]
+    marker: PhantomData<T>,
+}
+
+#[derive(Builder)]
+pub struct Slot<T> {
+    id: u64,
+    #[builder(skip)]
+    marker: PhantomData<T>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Tag<T> {
+    #[builder(skip)]
+    marker: PhantomData<T>,
+}
+
+// tuple fields and the immutable pattern, which clones the builder
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Counter(#[builder(name = "start")] u64, #[builder(skip = "1")] u64);
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.runs, 0);
+    assert_eq!(command.cache_file, "cargo.cache");
+
+    // the skipped fields are recomputed rather than carried over
+    let mut command = command;
+    command.runs = 3;
+    let rebuilt = command.to_builder().executable("rustc".to_owned()).build().unwrap();
+    assert_eq!(rebuilt.runs, 0);
+    assert_eq!(rebuilt.cache_file, "rustc.cache");
+
+    let handle: Handle<String> = Handle::builder().id(7).build().unwrap();
+    assert_eq!(handle.id, 7);
+    let _: PhantomData<String> = handle.marker;
+
+    let mut builder = Slot::<String>::builder();
+    builder.id(1).merge(SlotBuilder::new());
+    let slot = builder.build().unwrap();
+    assert_eq!(slot.id, 1);
+    let _: PhantomData<String> = slot.to_builder().build().unwrap().marker;
+
+    let tag: Tag<u8> = Tag::builder().clone().build().unwrap();
+    let _: PhantomData<u8> = tag.marker;
+
+    let counter = Counter::builder().start(10).build().unwrap();
+    assert_eq!(counter.0, 10);
+    assert_eq!(counter.1, 1);
+}
@@ -0,0 +1,72 @@
+// Setters can be renamed. A struct-level #[builder(setter(prefix = "..."))]
+// goes in front of every setter name, #[builder(setter(name = "..."))] on a
+// field replaces its setter's name, and #[builder(alias = "...")] keeps extra
+// setters under old names around, marked #[deprecated]. Raw identifiers lose
+// their `r#` when a prefix is put in front of them.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(setter(prefix = "with_"))]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    #[builder(setter(name = "cwd"), alias = "working_dir")]
+    current_dir: Option<String>,
+    r#type: String,
+}
+
+#[derive(Builder)]
+pub struct Token {
+    r#type: String,
+    #[builder(setter(prefix = "set_"))]
+    r#ref: Option<String>,
+    #[builder(alias = "length", alias = "size")]
+    len: usize,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    #[builder(alias = "hostname")]
+    host: String,
+}
+
+#[allow(deprecated)]
+fn main() {
+    let command = Command::builder()
+        .with_executable("cargo".to_owned())
+        .arg("build".to_owned())
+        .with_args(vec!["test".to_owned()])
+        .extend_args(vec!["--release".to_owned()])
+        .cwd("..".to_owned())
+        .with_type("bin".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.args, vec!["test", "--release"]);
+    assert_eq!(command.current_dir.as_deref(), Some(".."));
+    assert_eq!(command.r#type, "bin");
+
+    let command = Command::builder()
+        .with_executable("cargo".to_owned())
+        .working_dir("/".to_owned())
+        .with_type("lib".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.current_dir.as_deref(), Some("/"));
+
+    let token = Token::builder()
+        .r#type("ident".to_owned())
+        .set_ref("x".to_owned())
+        .size(1)
+        .build()
+        .unwrap();
+    assert_eq!(token.r#type, "ident");
+    assert_eq!(token.r#ref.as_deref(), Some("x"));
+    assert_eq!(token.len, 1);
+
+    let server = Server::builder().hostname("localhost".to_owned()).build().unwrap();
+    assert_eq!(server.host, "localhost");
+}
@@ -0,0 +1,54 @@
+// Besides the setter taking a `T`, an `Option<T>` field gets `field_opt`
+// taking the `Option<T>` as is and `clear_field` unsetting it again. With
+// #[builder(setter(strip_option = false))] the plain setter takes the
+// `Option<T>` itself.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    current_dir: Option<String>,
+    #[builder(setter(strip_option = false, into))]
+    env: Option<String>,
+    #[builder(default = "Some(30)")]
+    timeout: Option<u64>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned", setter(prefix = "with_"))]
+pub struct Request {
+    #[builder(setter(name = "body"))]
+    payload: Option<Vec<u8>>,
+}
+
+fn main() {
+    let previous: Option<String> = Some("/tmp".to_owned());
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .current_dir_opt(previous)
+        .env(None::<String>)
+        .build()
+        .unwrap();
+    assert_eq!(command.current_dir.as_deref(), Some("/tmp"));
+    assert_eq!(command.env, None);
+
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .current_dir("/tmp".to_owned())
+        .clear_current_dir()
+        .env(Some("RUST_LOG=debug".to_owned()))
+        .timeout(5)
+        .clear_timeout()
+        .build()
+        .unwrap();
+    assert_eq!(command.current_dir, None);
+    assert_eq!(command.env.as_deref(), Some("RUST_LOG=debug"));
+    // an unset field with a default falls back to it
+    assert_eq!(command.timeout, Some(30));
+
+    let request = Request::builder().body(vec![1]).clear_body().build().unwrap();
+    assert_eq!(request.payload, None);
+    let request = Request::builder().body_opt(Some(vec![2])).build().unwrap();
+    assert_eq!(request.payload, Some(vec![2]));
+}
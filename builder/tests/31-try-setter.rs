@@ -0,0 +1,53 @@
+// #[builder(try_setter)] adds a `try_field` setter next to the plain one which
+// takes anything convertible with `TryInto`. A failed conversion comes back as
+// the builder's error, the same one build() returns.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug)]
+pub struct Server {
+    host: String,
+    #[builder(try_setter)]
+    port: u16,
+    #[builder(try_setter)]
+    workers: Option<u8>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Connection {
+    #[builder(try_setter, setter(strip_option = false))]
+    timeout: Option<u32>,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Listener {
+    #[builder(try_setter, setter(name = "backlog_size"))]
+    backlog: u16,
+}
+
+fn configure(port: u64) -> Result<Server, ServerBuilderError> {
+    Server::builder()
+        .host("localhost".to_owned())
+        .try_port(port)?
+        .try_workers(4i64)?
+        .build()
+}
+
+fn main() {
+    let server = configure(8080).unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.workers, Some(4));
+
+    let err = configure(70000).unwrap_err();
+    assert!(matches!(err, ServerBuilderError::ValidationError(_)));
+    assert!(err.to_string().starts_with("invalid value for field `port`"));
+
+    let connection = Connection::builder().try_timeout(30u64).unwrap().build().unwrap();
+    assert_eq!(connection.timeout, Some(30));
+    assert!(Connection::builder().try_timeout(-1i64).is_err());
+
+    let listener = Listener::builder().try_backlog_size(128u32).unwrap().build().unwrap();
+    assert_eq!(listener.backlog, 128);
+}
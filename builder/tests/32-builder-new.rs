@@ -0,0 +1,80 @@
+// Builders implement Default and have a `new()` constructor, both handing out
+// a builder with nothing set, the same as `Command::builder()`. Field defaults
+// still only apply in build(). A setter called `new` wins over `new()`.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(default = "vec![\"--verbose\".to_owned()]")]
+    args: Vec<String>,
+}
+
+#[derive(Builder)]
+#[builder(constructor = false)]
+pub struct Wrapper<T> {
+    value: T,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    host: String,
+}
+
+#[derive(Builder)]
+#[builder(derive(::core::default::Default))]
+pub struct Derived {
+    value: Option<u8>,
+}
+
+#[derive(Builder)]
+pub struct Opts {
+    new: bool,
+}
+
+#[derive(Builder)]
+pub struct Legacy {
+    #[builder(alias = "new")]
+    fresh: Option<bool>,
+}
+
+// builders can be held in types that derive Default themselves
+#[derive(Default)]
+pub struct Config {
+    command: CommandBuilder,
+    wrapper: WrapperBuilder<u8>,
+}
+
+fn fresh<B: Default>() -> B {
+    B::default()
+}
+
+fn main() {
+    let command = CommandBuilder::new().executable("cargo".to_owned()).build().unwrap();
+    assert_eq!(command.args, vec!["--verbose"]);
+
+    let mut builder: CommandBuilder = fresh();
+    assert!(builder.build().is_err());
+    let command = builder.executable("rustc".to_owned()).build().unwrap();
+    assert_eq!(command.executable, "rustc");
+
+    let wrapper = WrapperBuilder::new().value("x").build().unwrap();
+    assert_eq!(wrapper.value, "x");
+
+    let server = ServerBuilder::default().host("localhost".to_owned()).build().unwrap();
+    assert_eq!(server.host, "localhost");
+
+    assert_eq!(DerivedBuilder::default().value(1).build().unwrap().value, Some(1));
+
+    let opts = OptsBuilder::default().new(true).build().unwrap();
+    assert!(opts.new);
+    #[allow(deprecated)]
+    let legacy = Legacy::builder().new(true).build().unwrap();
+    assert_eq!(legacy.fresh, Some(true));
+
+    let mut config = Config::default();
+    assert!(config.command.build().is_err());
+    assert_eq!(config.wrapper.value(1).build().unwrap().value, 1);
+}
@@ -0,0 +1,65 @@
+// With #[builder(no_std)] the generated code only refers to `::core` and `::alloc`. The error
+// type still implements `Error`, through `core::error::Error`.
+//
+// This crate is no_std; `std` is only linked from a module so that the test
+// can still run, which keeps `::std` paths from resolving at the crate root.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use derive_builder::Builder;
+
+mod host {
+    extern crate std;
+
+    pub fn assert_error<E: std::error::Error>() {}
+}
+
+#[derive(Builder)]
+#[builder(no_std)]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    #[builder(try_setter)]
+    retries: Option<u8>,
+    #[builder(default)]
+    timeout: u64,
+}
+
+#[derive(Builder)]
+#[builder(no_std, typestate, pattern = "owned")]
+pub struct Server {
+    host: String,
+    #[builder(try_setter)]
+    port: u16,
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_string())
+        .arg("build".to_string())
+        .try_retries(3u32)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(command.args, ["build"]);
+    assert_eq!(command.retries, Some(3));
+    assert_eq!(command.timeout, 0);
+
+    let err = CommandBuilder::new().build().err().unwrap();
+    assert_eq!(err, CommandBuilderError::MissingExecutable);
+    assert_eq!(err.to_string(), "Field `executable` is missing");
+    host::assert_error::<CommandBuilderError>();
+
+    let server = Server::builder()
+        .host("localhost".to_string())
+        .try_port(8080u32)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(server.port, 8080);
+}
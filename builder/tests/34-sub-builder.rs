@@ -0,0 +1,91 @@
+// A field whose type derives Builder too can be marked
+// #[builder(sub_builder)]. Its slot then holds the nested builder, edited
+// through `tls(|b| ...)` or `tls_mut()`, and build() builds it along with the
+// outer value. A field missing in the nested builder is reported with its
+// dotted path. This works for mutable, owned and typestate builders; the
+// immutable pattern rejects `sub_builder` (tests/39-sub-builder-immutable.rs).
+
+use derive_builder::Builder;
+
+#[derive(Builder, Clone, Debug, PartialEq)]
+pub struct Key {
+    path: String,
+    #[builder(default)]
+    password: Option<String>,
+}
+
+#[derive(Builder, Clone, Debug, PartialEq)]
+pub struct TlsConfig {
+    cert: String,
+    #[builder(sub_builder)]
+    key: Key,
+}
+
+#[derive(Builder, Debug)]
+pub struct Server {
+    host: String,
+    #[builder(sub_builder)]
+    tls: TlsConfig,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Proxy {
+    #[builder(sub_builder)]
+    upstream: TlsConfig,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Gateway {
+    name: String,
+    #[builder(sub_builder)]
+    tls: TlsConfig,
+}
+
+fn main() {
+    let server = Server::builder()
+        .host("localhost".to_owned())
+        .tls(|b| b.cert("cert.pem".to_owned()).key(|k| k.path("key.pem".to_owned())))
+        .build()
+        .unwrap();
+    assert_eq!(server.tls.cert, "cert.pem");
+    assert_eq!(server.tls.key.path, "key.pem");
+
+    let mut builder = Server::builder();
+    builder.host("localhost".to_owned());
+    builder.tls_mut().cert("cert.pem".to_owned());
+    let err = builder.build().unwrap_err();
+    assert_eq!(
+        err,
+        ServerBuilderError::InvalidTls(TlsConfigBuilderError::InvalidKey(KeyBuilderError::MissingPath))
+    );
+    assert_eq!(err.missing_field().as_deref(), Some("tls.key.path"));
+    assert_eq!(err.to_string(), "Field `tls.key.path` is missing");
+
+    builder.tls_mut().key_mut().path("key.pem".to_owned());
+    assert!(builder.build().is_ok());
+
+    // a nested value goes back into its nested builder
+    let mut builder = server.to_builder();
+    builder.tls(|b| b.cert("other.pem".to_owned()));
+    let server = builder.build().unwrap();
+    assert_eq!(server.tls.cert, "other.pem");
+    assert_eq!(server.tls.key.path, "key.pem");
+
+    let proxy = Proxy::builder()
+        .upstream(|b| b.cert("cert.pem".to_owned()).key(|k| k.path("key.pem".to_owned())))
+        .build()
+        .unwrap();
+    assert_eq!(proxy.upstream.cert, "cert.pem");
+    assert!(Proxy::builder().build().is_err());
+
+    let gateway = Gateway::builder()
+        .tls(|b| b.cert("cert.pem".to_owned()).key(|k| k.path("key.pem".to_owned())))
+        .name("edge".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(gateway.tls.key.path, "key.pem");
+    let err = Gateway::builder().name("edge".to_owned()).build().err().unwrap();
+    assert_eq!(err.missing_field().as_deref(), Some("tls.cert"));
+}
@@ -0,0 +1,100 @@
+// Builders can tell what is left to do before build() succeeds:
+// `missing_fields()` lists the required fields that are not set yet,
+// `is_complete()` checks that there are none, and the `FIELDS` constant
+// describes every field that has a setter. A field whose setter is named
+// `missing_fields` or `is_complete` keeps it instead.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+    #[builder(default = "30")]
+    timeout: u64,
+    user: String,
+    #[builder(skip)]
+    runs: u32,
+}
+
+#[derive(Builder)]
+pub struct Key {
+    path: String,
+}
+
+#[derive(Builder)]
+pub struct Tls {
+    #[builder(sub_builder)]
+    key: Key,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+// a `()` field looks the same set or unset to the type system
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Marker {
+    marker: (),
+}
+
+#[derive(Builder)]
+pub struct Task {
+    is_complete: bool,
+    #[builder(setter(name = "missing_fields"))]
+    missing: Vec<String>,
+}
+
+fn main() {
+    let mut builder = Command::builder();
+    assert_eq!(builder.missing_fields(), ["executable", "user"]);
+    assert!(!builder.is_complete());
+    builder.executable("cargo".to_owned());
+    assert_eq!(builder.missing_fields(), ["user"]);
+    builder.user("root".to_owned());
+    assert!(builder.missing_fields().is_empty());
+    assert!(builder.is_complete());
+
+    let names: Vec<_> = CommandBuilder::FIELDS.iter().map(|field| field.name).collect();
+    assert_eq!(names, ["executable", "args", "current_dir", "timeout", "user"]);
+    assert_eq!(
+        CommandBuilder::FIELDS[1],
+        CommandBuilderFieldInfo {
+            name: "args",
+            ty: "Vec<String>",
+            kind: CommandBuilderFieldKind::Each,
+        }
+    );
+    assert_eq!(CommandBuilder::FIELDS[2].ty, "Option<String>");
+    assert_eq!(CommandBuilder::FIELDS[2].kind, CommandBuilderFieldKind::Optional);
+    assert_eq!(CommandBuilder::FIELDS[3].kind, CommandBuilderFieldKind::Optional);
+    assert_eq!(CommandBuilder::FIELDS[4].kind, CommandBuilderFieldKind::Required);
+
+    let mut builder = Tls::builder();
+    assert_eq!(builder.missing_fields(), ["key"]);
+    builder.key(|k| k.path("key.pem".to_owned()));
+    assert!(builder.is_complete());
+
+    let builder = Server::builder();
+    assert_eq!(builder.missing_fields(), ["host", "port"]);
+    let builder = builder.port(80);
+    assert_eq!(builder.missing_fields(), ["host"]);
+    assert!(builder.host("localhost".to_owned()).is_complete());
+
+    let builder = Marker::builder();
+    assert_eq!(builder.missing_fields(), ["marker"]);
+    let builder = builder.marker(());
+    assert!(builder.missing_fields().is_empty());
+    assert!(builder.is_complete());
+    assert!(builder.build().is_ok());
+
+    let task = Task::builder().is_complete(true).missing_fields(vec!["owner".to_owned()]).build().unwrap();
+    assert!(task.is_complete);
+    assert_eq!(task.missing, ["owner"]);
+}
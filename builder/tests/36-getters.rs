@@ -0,0 +1,58 @@
+// Every builder slot has a `get_field` getter reading back what was set so
+// far, e.g. to pick a default depending on another field. Unset slots read as
+// `None`, `each` collections are handed out as they are (a slice for a `Vec`)
+// and nested builders by reference.
+
+use derive_builder::Builder;
+use std::collections::HashSet;
+
+#[derive(Builder)]
+pub struct Server {
+    host: String,
+    port: u16,
+    #[builder(setter(name = "cwd"))]
+    current_dir: Option<String>,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    #[builder(each = "tag")]
+    tags: HashSet<String>,
+    #[builder(sub_builder)]
+    limits: Limits,
+}
+
+#[derive(Builder)]
+pub struct Limits {
+    #[builder(default = "64")]
+    connections: u32,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Endpoint {
+    url: String,
+}
+
+fn main() {
+    let mut builder = Server::builder();
+    assert_eq!(builder.get_host(), None);
+    assert_eq!(builder.get_cwd(), None);
+    builder.host("localhost".to_owned()).cwd("/srv".to_owned()).arg("-v".to_owned()).tag("web".to_owned());
+    if builder.get_host().map(String::as_str) == Some("localhost") && builder.get_port().is_none() {
+        builder.port(8080);
+    }
+    assert_eq!(builder.get_port(), Some(&8080));
+    assert_eq!(builder.get_cwd().map(String::as_str), Some("/srv"));
+    assert_eq!(builder.get_args(), ["-v"]);
+    assert!(builder.get_tags().contains("web"));
+    builder.limits(|l| l.connections(8));
+    assert_eq!(builder.get_limits().get_connections(), Some(&8));
+
+    let server = builder.build().unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.limits.connections, 8);
+
+    let endpoint = Endpoint::builder();
+    let _: &() = endpoint.get_url();
+    let endpoint = endpoint.url("http://localhost".to_owned());
+    assert_eq!(endpoint.get_url(), "http://localhost");
+}
@@ -0,0 +1,79 @@
+// `merge()` layers one builder over another: values set on the other builder
+// win, `each` collections are appended to unless marked
+// #[builder(merge = "replace")], and nested builders are merged field by
+// field. `merged()` does the same for a builder held by value. A field whose
+// setter is named `merge` or `merged` keeps it and there is no merging then.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Tls {
+    cert: String,
+    key: Option<String>,
+}
+
+#[derive(Builder)]
+pub struct Config {
+    host: String,
+    #[builder(default = "80")]
+    port: u16,
+    log_level: Option<String>,
+    #[builder(each = "include")]
+    includes: Vec<String>,
+    #[builder(each = "listen", merge = "replace")]
+    listens: Vec<String>,
+    #[builder(sub_builder)]
+    tls: Tls,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Flags {
+    verbose: Option<bool>,
+}
+
+#[derive(Builder)]
+pub struct Git {
+    merge: bool,
+    rebase: bool,
+}
+
+fn main() {
+    let mut defaults = Config::builder();
+    defaults
+        .host("0.0.0.0".to_owned())
+        .include("base.conf".to_owned())
+        .listen("0.0.0.0:80".to_owned())
+        .tls(|t| t.cert("default.pem".to_owned()).key("default.key".to_owned()));
+
+    let mut file = Config::builder();
+    file.port(8080)
+        .include("site.conf".to_owned())
+        .listen("127.0.0.1:8080".to_owned())
+        .tls(|t| t.cert("site.pem".to_owned()));
+
+    let mut cli = Config::builder();
+    cli.log_level("debug".to_owned());
+
+    let config = defaults.merge(file).merge(cli).build().unwrap();
+    assert_eq!(config.host, "0.0.0.0");
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.log_level.as_deref(), Some("debug"));
+    assert_eq!(config.includes, ["base.conf", "site.conf"]);
+    assert_eq!(config.listens, ["127.0.0.1:8080"]);
+    assert_eq!(config.tls.cert, "site.pem");
+    assert_eq!(config.tls.key.as_deref(), Some("default.key"));
+
+    // replacing with nothing keeps what was there
+    let mut base = Config::builder();
+    base.host("localhost".to_owned()).listen("[::]:80".to_owned()).tls(|t| t.cert("a.pem".to_owned()));
+    let config = base.merged(Config::builder()).build().unwrap();
+    assert_eq!(config.listens, ["[::]:80"]);
+
+    let flags = Flags::builder().verbose(true).merged(Flags::builder()).build().unwrap();
+    assert_eq!(flags.verbose, Some(true));
+
+    let git = Git::builder().merge(true).rebase(false).build().unwrap();
+    assert!(git.merge);
+    assert!(!git.rebase);
+}
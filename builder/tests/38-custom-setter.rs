@@ -0,0 +1,73 @@
+// #[builder(setter(custom))] keeps the field's slot and its handling in
+// build(), but leaves writing the setter to a hand-written impl block, e.g. to
+// normalize the value first. All other setters are still generated. A
+// typestate builder has no setter to write by hand that could move it to
+// the next state, so it gets a hidden `__set_field` to forward to instead.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(setter(custom))]
+    current_dir: Option<String>,
+    #[builder(setter(custom), each = "arg")]
+    args: Vec<String>,
+    #[builder(setter(custom))]
+    user: String,
+}
+
+impl CommandBuilder {
+    pub fn current_dir(&mut self, dir: &str) -> &mut Self {
+        self.current_dir = Some(dir.trim_end_matches('/').to_owned());
+        self
+    }
+
+    pub fn args(&mut self, line: &str) -> &mut Self {
+        self.args.extend(line.split_whitespace().map(str::to_owned));
+        self
+    }
+
+    pub fn user(&mut self, user: &str) -> &mut Self {
+        self.user = Some(user.to_lowercase());
+        self
+    }
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Login {
+    #[builder(setter(custom))]
+    user: String,
+    port: u16,
+}
+
+impl<P> LoginBuilder<(), P> {
+    pub fn user(self, user: &str) -> LoginBuilder<String, P> {
+        self.__set_user(user.to_lowercase())
+    }
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .current_dir("/srv/app/")
+        .args("build --release")
+        .user("Root")
+        .build()
+        .unwrap();
+    assert_eq!(command.current_dir.as_deref(), Some("/srv/app"));
+    assert_eq!(command.args, ["build", "--release"]);
+    assert_eq!(command.user, "root");
+
+    // a custom setter left uncalled is still reported as missing
+    let err = Command::builder().executable("cargo".to_owned()).build().err();
+    assert_eq!(err, Some(CommandBuilderError::MissingUser));
+
+    let builder = Login::builder().user("Root");
+    assert_eq!(builder.missing_fields(), ["port"]);
+    let builder = builder.port(22);
+    assert!(builder.is_complete());
+    let login = builder.build().unwrap();
+    assert_eq!((login.user.as_str(), login.port), ("root", 22));
+}
@@ -0,0 +1,18 @@
+// The immutable pattern only hands `&self` to setters and build(), which can't
+// edit or build a nested builder in place, so `sub_builder` is rejected there.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Tls {
+    cert: String,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Server {
+    #[builder(sub_builder)]
+    tls: Tls,
+}
+
+fn main() {}
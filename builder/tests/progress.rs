@@ -10,4 +10,34 @@ fn tests() {
     // t.pass("tests/07-repeated-field.rs");
     t.compile_fail("tests/08-unrecognized-attribute.rs");
     //t.pass("tests/09-redefined-prelude-types.rs");
+    t.pass("tests/10-typed-error.rs");
+    t.pass("tests/11-typestate.rs");
+    t.compile_fail("tests/12-typestate-missing-field.rs");
+    t.pass("tests/13-generics.rs");
+    t.pass("tests/14-tuple-and-enum.rs");
+    t.compile_fail("tests/15-union.rs");
+    t.pass("tests/16-default.rs");
+    t.pass("tests/17-setter-into.rs");
+    t.pass("tests/18-pattern.rs");
+    t.pass("tests/19-validate.rs");
+    t.pass("tests/20-each-collections.rs");
+    t.compile_fail("tests/21-each-unknown-element.rs");
+    t.pass("tests/22-each-bulk-setters.rs");
+    t.pass("tests/23-visibility.rs");
+    t.compile_fail("tests/24-private-setter.rs");
+    t.pass("tests/25-custom-names.rs");
+    t.pass("tests/26-round-trip.rs");
+    t.pass("tests/27-builder-attrs.rs");
+    t.pass("tests/28-skip.rs");
+    t.pass("tests/29-setter-names.rs");
+    t.pass("tests/30-option-setters.rs");
+    t.pass("tests/31-try-setter.rs");
+    t.pass("tests/32-builder-new.rs");
+    t.pass("tests/33-no-std.rs");
+    t.pass("tests/34-sub-builder.rs");
+    t.pass("tests/35-introspection.rs");
+    t.pass("tests/36-getters.rs");
+    t.pass("tests/37-merge.rs");
+    t.pass("tests/38-custom-setter.rs");
+    t.compile_fail("tests/39-sub-builder-immutable.rs");
 }